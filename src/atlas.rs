@@ -0,0 +1,287 @@
+// 字形缓存: 每个字符在每种字号、变换下只光栅化一次，之后直接按颜色混合覆盖率蒙版
+use std::{collections::HashMap, sync::Arc};
+
+use font_kit::font::Font;
+use raqote::{Color, DrawOptions, DrawTarget, Point, Source};
+
+use crate::config::{is_joiner, GlyphTransform};
+
+/// 一个字符的绘制方式: 字符、字体序号和变换
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Glyph {
+    //一个字素簇，可能由多个码位组成
+    pub tile: Arc<str>,
+    //在GlyphAtlas::fonts中的序号
+    pub font: usize,
+    pub transform: GlyphTransform,
+}
+
+/// 字形覆盖率蒙版，坐标相对于基线原点
+pub struct GlyphMask {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub coverage: Vec<u8>,
+}
+
+impl GlyphMask {
+    /// 没有像素的空白字形
+    fn empty() -> Self {
+        Self {
+            left: 0,
+            top: 0,
+            width: 0,
+            height: 0,
+            coverage: vec![],
+        }
+    }
+
+    /// 光栅化一个字符，多个码位的字符作为一个整体绘制在一个格子中
+    fn rasterize(font: &Font, tile: &str, font_size: f32) -> Self {
+        //基线原点留出足够的边距，防止字形超出画布
+        let size = (font_size * 2.0).ceil() as i32 + 4;
+        let (ox, oy) = (
+            (font_size / 2.0).ceil() as i32,
+            (font_size * 1.5).ceil() as i32,
+        );
+        //跳过连接符、变体选择符和字体中没有的码位(draw_text遇到没有的码位会panic)
+        let ids: Vec<u32> = tile
+            .chars()
+            .filter(|c| !is_joiner(*c))
+            .filter_map(|c| font.glyph_for_char(c))
+            .filter(|id| *id != 0)
+            .collect();
+        if ids.is_empty() {
+            return Self::empty();
+        }
+        let units_per_em = font.metrics().units_per_em as f32;
+        let advances: Vec<f32> = ids
+            .iter()
+            .map(|id| font.advance(*id).map_or(0.0, |a| a.x()) / units_per_em)
+            .collect();
+        //多个码位的总宽度超过一个格子时缩小
+        let width = advances.iter().sum::<f32>() * font_size;
+        let draw_size = if ids.len() > 1 && width > font_size {
+            font_size * font_size / width
+        } else {
+            font_size
+        };
+        let mut x = ox as f32;
+        let positions: Vec<Point> = advances
+            .iter()
+            .map(|advance| {
+                let p = Point::new(x, oy as f32);
+                x += advance * draw_size;
+                p
+            })
+            .collect();
+        let mut dt = DrawTarget::new(size, size);
+        dt.draw_glyphs(
+            font,
+            draw_size,
+            &ids,
+            &positions,
+            &Source::from(Color::new(255, 255, 255, 255)),
+            &DrawOptions::new(),
+        );
+
+        //裁剪到有像素的区域
+        let data = dt.get_data();
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (size, size, -1, -1);
+        for y in 0..size {
+            for x in 0..size {
+                if data[(y * size + x) as usize] >> 24 > 0 {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+        if max_x < 0 {
+            //空白字符
+            return Self::empty();
+        }
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+        let mut coverage = Vec::with_capacity((width * height) as usize);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                coverage.push((data[(y * size + x) as usize] >> 24) as u8);
+            }
+        }
+        Self {
+            left: min_x - ox,
+            top: min_y - oy,
+            width,
+            height,
+            coverage,
+        }
+    }
+
+    /// 以字符格子中心(font_size/2, -font_size/2)为中心做镜像、翻转或旋转
+    fn transformed(&self, transform: GlyphTransform, font_size: f32) -> Self {
+        //空白字符不需要变换
+        if self.width == 0 || self.height == 0 {
+            return Self::empty();
+        }
+        let c = (font_size / 2.0).round() as i32;
+        let (cx, cy) = (c, -c);
+        //像素(x, y)变换后的位置(都是左上角坐标)
+        let map = |x: i32, y: i32| match transform {
+            GlyphTransform::None => (x, y),
+            GlyphTransform::Mirror => (2 * cx - 1 - x, y),
+            GlyphTransform::Flip => (x, 2 * cy - 1 - y),
+            GlyphTransform::Rotate180 => (2 * cx - 1 - x, 2 * cy - 1 - y),
+            GlyphTransform::Rotate90 => (cx + cy - 1 - y, cy - cx + x),
+            GlyphTransform::Rotate270 => (cx - cy + y, cx + cy - 1 - x),
+        };
+        let (x0, y0) = map(self.left, self.top);
+        let (x1, y1) = map(self.left + self.width - 1, self.top + self.height - 1);
+        let (left, top) = (x0.min(x1), y0.min(y1));
+        let (width, height) = ((x1 - x0).abs() + 1, (y1 - y0).abs() + 1);
+        let mut coverage = vec![0u8; self.coverage.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (dx, dy) = map(self.left + x, self.top + y);
+                coverage[((dy - top) * width + dx - left) as usize] =
+                    self.coverage[(y * self.width + x) as usize];
+            }
+        }
+        Self {
+            left,
+            top,
+            width,
+            height,
+            coverage,
+        }
+    }
+
+    /// 以(x, y)为基线原点，用指定颜色混合到画布上
+    pub fn blit(&self, canvas: &mut DrawTarget, x: i32, y: i32, color: Color) {
+        let (cw, ch) = (canvas.width(), canvas.height());
+        let a = color.a() as u32;
+        if a == 0 || self.width == 0 || self.height == 0 {
+            return;
+        }
+        //预乘颜色
+        let (r, g, b) = (
+            color.r() as u32 * a / 255,
+            color.g() as u32 * a / 255,
+            color.b() as u32 * a / 255,
+        );
+        let data = canvas.get_data_mut();
+        for my in 0..self.height {
+            let py = y + self.top + my;
+            if py < 0 || py >= ch {
+                continue;
+            }
+            for mx in 0..self.width {
+                let px = x + self.left + mx;
+                if px < 0 || px >= cw {
+                    continue;
+                }
+                let c = self.coverage[(my * self.width + mx) as usize] as u32;
+                if c == 0 {
+                    continue;
+                }
+                let (sa, sr, sg, sb) = (a * c / 255, r * c / 255, g * c / 255, b * c / 255);
+                let inv = 255 - sa;
+                let idx = (py * cw + px) as usize;
+                let d = data[idx];
+                let blend = |s: u32, shift: u32| s + ((d >> shift) & 0xff) * inv / 255;
+                data[idx] = (blend(sa, 24) << 24)
+                    | (blend(sr, 16) << 16)
+                    | (blend(sg, 8) << 8)
+                    | blend(sb, 0);
+            }
+        }
+    }
+}
+
+pub struct GlyphAtlas {
+    //第一个为主字体，其他为字符集使用的字体
+    pub fonts: Vec<Font>,
+    glyphs: HashMap<(Glyph, u32), GlyphMask>,
+}
+
+impl GlyphAtlas {
+    /// 每次加载字体时重新创建
+    pub fn new(fonts: Vec<Font>) -> Self {
+        Self {
+            fonts,
+            glyphs: HashMap::new(),
+        }
+    }
+
+    pub fn glyph(&mut self, glyph: &Glyph, font_size: f32) -> &GlyphMask {
+        let base = Glyph {
+            transform: GlyphTransform::None,
+            ..glyph.clone()
+        };
+        let base_key = (base, font_size.to_bits());
+        if !self.glyphs.contains_key(&base_key) {
+            let font = &self.fonts[glyph.font.min(self.fonts.len() - 1)];
+            let mask = GlyphMask::rasterize(font, &glyph.tile, font_size);
+            self.glyphs.insert(base_key.clone(), mask);
+        }
+        let key = (glyph.clone(), font_size.to_bits());
+        if !self.glyphs.contains_key(&key) {
+            //变换后的字形由原字形生成
+            let mask = self.glyphs[&base_key].transformed(glyph.transform, font_size);
+            self.glyphs.insert(key.clone(), mask);
+        }
+        &self.glyphs[&key]
+    }
+
+    /// 绘制一个字符，pos为基线原点
+    pub fn draw(
+        &mut self,
+        canvas: &mut DrawTarget,
+        glyph: &Glyph,
+        font_size: f32,
+        pos: Point,
+        color: Color,
+    ) {
+        let (x, y) = (pos.x.round() as i32, pos.y.round() as i32);
+        self.glyph(glyph, font_size).blit(canvas, x, y, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_font;
+
+    fn same(a: &GlyphMask, b: &GlyphMask) -> bool {
+        (a.left, a.top, a.width, a.height) == (b.left, b.top, b.width, b.height)
+            && a.coverage == b.coverage
+    }
+
+    #[test]
+    fn joiners_and_selectors_are_skipped() {
+        let font = load_font("1").unwrap();
+        let digit = GlyphMask::rasterize(&font, "1", 16.0);
+        let selected = GlyphMask::rasterize(&font, "1\u{fe0f}", 16.0);
+        assert!(same(&digit, &selected));
+        let pair = GlyphMask::rasterize(&font, "10", 16.0);
+        let joined = GlyphMask::rasterize(&font, "1\u{200d}0", 16.0);
+        assert!(same(&pair, &joined));
+    }
+
+    #[test]
+    fn missing_cluster_is_empty() {
+        let font = load_font("1").unwrap();
+        //国旗和ZWJ表情序列不在点阵字体中
+        for tile in [
+            "\u{1f1e8}\u{1f1f3}",
+            "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}",
+        ] {
+            let mask = GlyphMask::rasterize(&font, tile, 16.0);
+            assert_eq!((mask.width, mask.height), (0, 0));
+            assert!(mask.coverage.is_empty());
+            let mirrored = mask.transformed(GlyphTransform::Mirror, 16.0);
+            assert!(mirrored.coverage.is_empty());
+        }
+    }
+}
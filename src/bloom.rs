@@ -0,0 +1,50 @@
+// 辉光: 提取亮部像素，模糊后叠加回画面，让头部的亮色字符发光
+use raqote::DrawTarget;
+
+use crate::{blur::box_blur, config::BloomConfig};
+
+pub struct Bloom {
+    config: BloomConfig,
+    //亮部缓冲区，大小与渲染画布相同
+    bright: Vec<u32>,
+}
+
+impl Bloom {
+    pub fn new(config: BloomConfig) -> Self {
+        Self {
+            config,
+            bright: vec![],
+        }
+    }
+
+    /// 在渲染分辨率下处理画布
+    pub fn apply(&mut self, canvas: &mut DrawTarget) {
+        let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+        let threshold = (self.config.threshold.clamp(0.0, 1.0) * 255.0) as u32;
+        let intensity = (self.config.intensity.max(0.0) * 256.0) as u32;
+        let data = canvas.get_data_mut();
+
+        //亮度超过阈值的像素
+        self.bright.clear();
+        self.bright.extend(data.iter().map(|&p| {
+            let luma = (((p >> 16) & 0xff) * 77 + ((p >> 8) & 0xff) * 150 + (p & 0xff) * 29) >> 8;
+            if luma >= threshold {
+                p
+            } else {
+                0
+            }
+        }));
+        box_blur(&mut self.bright, width, height, self.config.radius as usize);
+
+        //叠加回画面(预乘颜色，各通道不超过alpha)
+        for (d, &b) in data.iter_mut().zip(&self.bright) {
+            if b == 0 {
+                continue;
+            }
+            let add =
+                |shift: u32| ((*d >> shift) & 0xff) + ((((b >> shift) & 0xff) * intensity) >> 8);
+            let a = add(24).min(255);
+            *d = (a << 24) | (add(16).min(a) << 16) | (add(8).min(a) << 8) | add(0).min(a);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+// 可分离的盒式模糊，处理DrawTarget的预乘ARGB像素
+
+/// 对像素做半径为radius的盒式模糊(先横向再纵向)
+pub fn box_blur(data: &mut [u32], width: usize, height: usize, radius: usize) {
+    if radius == 0 || width == 0 || height == 0 {
+        return;
+    }
+    let mut tmp = vec![0u32; data.len()];
+    //横向: 每行一条线，相邻像素间隔1
+    blur_pass(data, &mut tmp, width, height, width, 1, radius);
+    //纵向: 每列一条线，相邻像素间隔width
+    blur_pass(&tmp, data, height, width, 1, width, radius);
+}
+
+/// 沿一个方向做滑动窗口平均，边缘外的像素按边缘像素计算
+fn blur_pass(
+    src: &[u32],
+    dst: &mut [u32],
+    len: usize,
+    lines: usize,
+    line_stride: usize,
+    step: usize,
+    radius: usize,
+) {
+    let window = (radius * 2 + 1) as u32;
+    let last = len as isize - 1;
+    for line in 0..lines {
+        let base = line * line_stride;
+        let pixel = |i: isize| src[base + i.clamp(0, last) as usize * step];
+
+        let mut sum = [0u32; 4];
+        for i in -(radius as isize)..=radius as isize {
+            add(&mut sum, pixel(i), true);
+        }
+        for i in 0..len {
+            dst[base + i * step] = pack(&sum, window);
+            let i = i as isize;
+            add(&mut sum, pixel(i - radius as isize), false);
+            add(&mut sum, pixel(i + radius as isize + 1), true);
+        }
+    }
+}
+
+fn add(sum: &mut [u32; 4], p: u32, plus: bool) {
+    for (k, s) in sum.iter_mut().enumerate() {
+        let v = (p >> (24 - k * 8)) & 0xff;
+        if plus {
+            *s += v;
+        } else {
+            *s -= v;
+        }
+    }
+}
+
+fn pack(sum: &[u32; 4], window: u32) -> u32 {
+    sum.iter()
+        .enumerate()
+        .fold(0, |p, (k, s)| p | ((s / window) << (24 - k * 8)))
+}
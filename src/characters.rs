@@ -1,165 +1,364 @@
-use std::time::{Duration, Instant};
+use std::{sync::Arc, time::Duration};
 
 use font_kit::font::Font;
-use rand::{prelude::ThreadRng, Rng};
-use raqote::{Color, DrawOptions, DrawTarget, Point, Source};
-
-use crate::config::Config;
-
-pub struct Character {
-    pub pos: Point,
-    pub font_size: f32,
-    pub tile: String,
-    pub color: Color,
-    pub light_color: Color,
-    // 0->绘制闪光 1->绘制文本
-    pub step: u8,
-    pub options: DrawOptions,
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use raqote::Color;
+
+use crate::{
+    atlas::Glyph,
+    config::{
+        has_glyphs, split_tiles, Config, GlyphTransform, LayerConfig, PaletteMode, CHARACTERS_01,
+    },
+    grid::Grid,
+};
+
+//mutation_rate按此帧间隔(秒)计算
+const BASE_FRAME: f32 = 0.05;
+
+/// 一个字符集的字符，每个字符已经选好字体和变换
+#[derive(Clone)]
+pub struct Charset {
+    pub glyphs: Vec<Glyph>,
 }
 
-impl Default for Character {
-    fn default() -> Self {
-        Self {
-            pos: Point::new(0., 0.),
-            font_size: 14.0,
-            tile: "A".to_string(),
-            color: Color::new(255, 255, 255, 255),
-            light_color: Color::new(255, 255, 255, 255),
-            options: DrawOptions::default(),
-            step: 0,
-        }
-    }
+/// 所有字符集，加载字体后创建一次
+#[derive(Clone)]
+pub struct Charsets {
+    pub sets: Vec<Charset>,
+    //只有一个字符集时为None
+    weights: Option<WeightedIndex<f32>>,
+    //所有字体中都没有的字符
+    pub uncovered: Vec<Arc<str>>,
 }
 
-/// 字符渲染逻辑: 绘制一次闪光色，绘制一次文字色
-impl Character {
-    pub fn update(&mut self) {
-        if self.step < 5 {
-            self.step += 1;
+impl Charsets {
+    /// fonts为config.fonts()对应的字体，每个字符使用字符集的字体或备用字体中第一个有该字符的字体
+    pub fn load(cfg: &Config, fonts: &[Font]) -> Self {
+        let chain = cfg.fallback_chain();
+        let mut sets = vec![];
+        let mut weights = vec![];
+        let mut uncovered = vec![];
+        for set in cfg.charsets() {
+            if set.weight <= 0.0 {
+                continue;
+            }
+            let mut order = vec![cfg.font_index(&set.font)];
+            order.extend(chain.iter().copied());
+            let transform = set.transform.unwrap_or(cfg.glyph.transform);
+            let mut glyphs = vec![];
+            for tile in set.tiles() {
+                match find_font(fonts, &order, &tile) {
+                    Some(font) => glyphs.push(Glyph {
+                        tile,
+                        font,
+                        transform,
+                    }),
+                    //没有字体可以显示的字符不参与代码雨，只用于提示
+                    None => {
+                        if !uncovered.contains(&tile) {
+                            uncovered.push(tile);
+                        }
+                    }
+                }
+            }
+            if !glyphs.is_empty() {
+                sets.push(Charset { glyphs });
+                weights.push(set.weight);
+            }
+        }
+        //所有字符集都为空时直接使用字符(没有字体的字符绘制为空白)
+        if sets.is_empty() {
+            let mut tiles = split_tiles(&cfg.characters_plain());
+            if tiles.is_empty() {
+                tiles = split_tiles(CHARACTERS_01);
+            }
+            let glyphs = tiles
+                .into_iter()
+                .map(|tile| Glyph {
+                    tile,
+                    font: 0,
+                    transform: cfg.glyph.transform,
+                })
+                .collect();
+            sets.push(Charset { glyphs });
+        }
+        //只有一个字符集时不需要按权重选择
+        let weights = if sets.len() > 1 {
+            WeightedIndex::new(weights).ok()
+        } else {
+            None
+        };
+        Self {
+            sets,
+            weights,
+            uncovered,
         }
     }
 
-    pub fn draw(&self, canvas: &mut DrawTarget, font: &Font) -> i32 {
-        let mut count = 0;
-        if self.step == 0 {
-            canvas.draw_text(
-                font,
-                self.font_size,
-                &self.tile,
-                self.pos,
-                &Source::from(self.light_color),
-                &self.options,
-            );
-            count += 1;
-        } else if self.step == 1 {
-            canvas.draw_text(
-                font,
-                self.font_size,
-                &self.tile,
-                self.pos,
-                &Source::from(self.color),
-                &self.options,
-            );
-            count += 1;
-        }
-        count
+    /// 按权重选择字符集，再从中随机选择一个字符
+    pub fn random_glyph(&self, rng: &mut impl Rng) -> Glyph {
+        let set = match &self.weights {
+            Some(weights) => &self.sets[weights.sample(rng)],
+            None => &self.sets[0],
+        };
+        set.glyphs[rng.gen_range(0..set.glyphs.len())].clone()
     }
 }
 
+/// 按order中的顺序查找第一个有该字符的字体
+pub fn find_font(fonts: &[Font], order: &[usize], tile: &str) -> Option<usize> {
+    order
+        .iter()
+        .copied()
+        .find(|i| fonts.get(*i).is_some_and(|f| has_glyphs(f, tile)))
+}
+
+/// 沿一条通道下落的字符串，按速度在网格中写入新字符
 pub struct CharacterString {
-    rng: ThreadRng,
-    //当前显示的字符
-    pub characters: Vec<Character>,
-    //最大字符串长度(屏幕高度/字体大小)
+    rng: StdRng,
+    //所在通道(竖直方向为列，水平方向为行)
+    pub lane: usize,
+    //最大字符串长度(通道长度)
     pub max_len: usize,
     //当前绘制的位置
     pub current_index: usize,
+    //头部位置(可以是小数)
+    pub position: f32,
+    //当前速度(每秒前进的字符数)
+    pub speed: f32,
+    //每秒速度变化量
+    pub acceleration: f32,
+    //速度范围
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
     pub mutation_rate: f32,
-    pub x: f32,
-    pub fade_speed: i32,
-    pub font_size: f32,
-    pub spacing: u32,
-    pub color: Color,
-    pub light_color: Color,
-    pub tiles: Vec<char>,
-    //随机延时
+    pub charsets: Charsets,
+    //随机字形变换
+    pub random_transforms: Vec<GlyphTransform>,
+    pub random_transform_rate: f32,
+    //调色板，为空时使用文字颜色
+    pub palette: Vec<Color>,
+    pub palette_mode: PaletteMode,
+    //本列或本次下落的颜色
+    pub color: Option<Color>,
+    //平滑滚动模式
+    pub smooth: bool,
+    //平滑滚动时单独绘制的头部字符
+    pub head_glyph: Glyph,
+    //是否正在下落(不在随机延时中)
+    pub falling: bool,
+    //随机延时(模拟时钟的时间)
     delay_time: Duration,
-    start_time: Instant,
+    start_time: Duration,
 }
 
 impl CharacterString {
-    pub fn update(&mut self) {
-        if self.start_time.elapsed() < self.delay_time {
-            return;
+    pub fn new(
+        cfg: &Config,
+        layer: &LayerConfig,
+        lane: usize,
+        max_len: usize,
+        rng: StdRng,
+        charsets: &Charsets,
+    ) -> Self {
+        let (min_speed, max_speed) = cfg.speed.range();
+        //景深层的速度倍数
+        let (min_speed, max_speed) = (min_speed * layer.speed, max_speed * layer.speed);
+        let mut st = Self {
+            rng,
+            lane,
+            max_len,
+            current_index: 0,
+            position: 0.0,
+            speed: min_speed,
+            acceleration: 0.0,
+            min_speed,
+            max_speed,
+            max_acceleration: (cfg.speed.acceleration * layer.speed).abs(),
+            mutation_rate: cfg.mutation_rate,
+            charsets: charsets.clone(),
+            random_transforms: cfg.glyph.random_transforms.clone(),
+            random_transform_rate: cfg.glyph.random_rate,
+            palette: cfg.palette(),
+            palette_mode: cfg.palette.mode,
+            color: None,
+            smooth: cfg.smooth,
+            head_glyph: Glyph {
+                tile: Arc::from(" "),
+                font: 0,
+                transform: GlyphTransform::None,
+            },
+            falling: false,
+            delay_time: Duration::from_secs(0),
+            start_time: Duration::ZERO,
+        };
+        st.reset_speed();
+        st.head_glyph = st.random_glyph();
+        st.color = st.random_color();
+        st
+    }
+
+    fn random_color(&mut self) -> Option<Color> {
+        if self.palette.is_empty() {
+            None
+        } else {
+            Some(self.palette[self.rng.gen_range(0..self.palette.len())])
         }
-        for c in &mut self.characters {
-            c.update();
+    }
+
+    /// 按权重选择字符集，再从中随机选择字符和变换
+    fn random_glyph(&mut self) -> Glyph {
+        let glyph = self.charsets.random_glyph(&mut self.rng);
+        Glyph {
+            transform: self.random_transform(glyph.transform),
+            ..glyph
         }
-        self.characters.retain(|c| c.step < 2);
+    }
 
-        if self.current_index < self.max_len {
-            //没有绘制结束，继续添加字符
-            let y = self.current_index as f32 * (self.font_size + self.spacing as f32);
-            let c = self.tiles[self.rng.gen_range(0..self.tiles.len())];
-            self.characters.push(Character {
-                pos: Point::new(self.x, y),
-                tile: format!("{c}"),
-                color: self.color,
-                light_color: self.light_color,
-                font_size: self.font_size,
-                ..Default::default()
-            });
-            self.current_index += 1;
+    /// 按概率使用随机变换，否则使用字符集的变换
+    fn random_transform(&mut self, transform: GlyphTransform) -> GlyphTransform {
+        if !self.random_transforms.is_empty() && self.rng.gen::<f32>() < self.random_transform_rate
+        {
+            self.random_transforms[self.rng.gen_range(0..self.random_transforms.len())]
         } else {
-            //已经绘制结束，检查是否所有字符都已消失
-            if self.characters.len() == 0 {
-                //重新开始新的一轮
-                self.current_index = 0;
-                //延迟1~7秒
-                self.start_time = Instant::now();
-                self.delay_time = Duration::from_millis(self.rng.gen_range(1000..7000));
-            }
+            transform
         }
     }
 
-    pub fn draw(&self, canvas: &mut DrawTarget, font: &Font) -> i32 {
-        let mut count = 0;
-        for c in &self.characters {
-            count += c.draw(canvas, font);
+    /// 平滑滚动时的头部字符及其位置(可以是小数)
+    pub fn head(&self) -> Option<(&Glyph, f32)> {
+        if self.smooth && self.falling && self.current_index < self.max_len {
+            Some((&self.head_glyph, self.position))
+        } else {
+            None
         }
-        count
     }
-}
-
-pub fn init(cfg: &Config, width: u32, height: u32) -> Vec<CharacterString> {
-    let font_size = cfg.font_size;
 
-    let color = cfg.color();
+    /// 为新的一轮随机选择速度和加速度
+    fn reset_speed(&mut self) {
+        self.speed = if self.max_speed > self.min_speed {
+            self.rng.gen_range(self.min_speed..self.max_speed)
+        } else {
+            self.min_speed
+        };
+        self.acceleration = if self.max_acceleration > 0.0 {
+            self.rng
+                .gen_range(-self.max_acceleration..self.max_acceleration)
+        } else {
+            0.0
+        };
+    }
 
-    let mut strings = vec![];
+    /// now: 模拟时钟的当前时间 dt: 距上一帧的秒数
+    pub fn update(&mut self, grid: &mut Grid, now: Duration, dt: f32) {
+        //残影中的字符按突变速率随机替换，突变速率按默认帧间隔计算，换算成本帧的概率
+        let mutation_rate = 1.0 - (1.0 - self.mutation_rate).powf(dt / BASE_FRAME);
+        for index in 0..self.max_len {
+            let (col, row) = match grid.lane_cell(self.lane, index) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            if let Some(cell) = grid.get_mut(col, row) {
+                if !cell.is_empty() && cell.age > 0.0 && self.rng.gen::<f32>() < mutation_rate {
+                    cell.glyph = Some(self.random_glyph());
+                }
+            }
+        }
 
-    let columns = width / font_size as u32;
-    let rows = (height / (font_size as u32 + cfg.spaceing)) + 1;
-    // println!("{width}x{height} 列数{columns}行数:{rows}");
+        if now.saturating_sub(self.start_time) < self.delay_time {
+            return;
+        }
+        self.falling = true;
 
-    for col in 0..columns {
-        strings.push(CharacterString {
-            rng: rand::thread_rng(),
-            font_size: cfg.font_size as f32,
-            color,
-            light_color: cfg.light_color(),
-            mutation_rate: cfg.mutation_rate,
-            tiles: cfg.characters_plain().chars().collect(),
-            characters: vec![],
-            max_len: rows as usize,
-            current_index: 0,
-            x: col as f32 * font_size as f32,
-            fade_speed: cfg.fade_speed,
-            spacing: cfg.spaceing,
-            delay_time: Duration::from_secs(0),
-            start_time: Instant::now(),
-        });
+        if self.current_index < self.max_len {
+            //没有绘制结束，按速度前进，经过的格子都添加字符
+            self.position += self.speed * dt;
+            //平滑滚动时头部完全离开格子后才留下残影
+            let passed = if self.smooth {
+                self.position - 1.0
+            } else {
+                self.position
+            };
+            while self.current_index < self.max_len && (self.current_index as f32) < passed {
+                let glyph = if self.smooth {
+                    let next = self.random_glyph();
+                    std::mem::replace(&mut self.head_glyph, next)
+                } else {
+                    self.random_glyph()
+                };
+                if let Some((col, row)) = grid.lane_cell(self.lane, self.current_index) {
+                    grid.set(col, row, glyph, self.color, self.speed);
+                }
+                self.current_index += 1;
+            }
+            self.speed =
+                (self.speed + self.acceleration * dt).clamp(self.min_speed, self.max_speed);
+        } else {
+            //已经绘制结束，重新开始新的一轮(残影由网格负责淡出)
+            self.current_index = 0;
+            self.position = 0.0;
+            self.falling = false;
+            self.reset_speed();
+            if self.palette_mode == PaletteMode::Drop {
+                self.color = self.random_color();
+            }
+            //延迟1~7秒
+            self.start_time = now;
+            self.delay_time = Duration::from_millis(self.rng.gen_range(1000..7000));
+        }
     }
+}
 
-    strings
+/// 每个通道的随机数生成器由所在层的种子和通道号决定，相同种子每次得到相同的结果
+fn lane_rng(seed: u64, lane: usize) -> StdRng {
+    StdRng::seed_from_u64(seed.wrapping_add(lane as u64))
+}
+
+pub fn init(
+    cfg: &Config,
+    layer: &LayerConfig,
+    grid: &Grid,
+    seed: u64,
+    charsets: &Charsets,
+) -> Vec<CharacterString> {
+    // println!("列数{}行数:{}", grid.columns, grid.rows);
+    let len = grid.lane_len();
+    (0..grid.lanes())
+        .map(|lane| CharacterString::new(cfg, layer, lane, len, lane_rng(seed, lane), charsets))
+        .collect()
+}
+
+/// 网格大小改变后，增减列数并更新每列的长度，已有的列保持当前状态
+pub fn resize(
+    strings: &mut Vec<CharacterString>,
+    cfg: &Config,
+    layer: &LayerConfig,
+    grid: &Grid,
+    seed: u64,
+    charsets: &Charsets,
+) {
+    let len = grid.lane_len();
+    strings.truncate(grid.lanes());
+    for lane in strings.len()..grid.lanes() {
+        strings.push(CharacterString::new(
+            cfg,
+            layer,
+            lane,
+            len,
+            lane_rng(seed, lane),
+            charsets,
+        ));
+    }
+    for st in strings.iter_mut() {
+        st.max_len = len;
+        if st.current_index > st.max_len {
+            st.current_index = st.max_len;
+            st.position = st.max_len as f32;
+        }
+    }
 }
@@ -0,0 +1,48 @@
+// 模拟时钟: 代码雨的随机延时等都从这里取时间，可以替换成固定步长的时钟来得到可重复的结果
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+    /// 推进一帧，返回模拟开始后经过的时间
+    fn tick(&mut self) -> Duration;
+}
+
+/// 使用系统时间
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn tick(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// 每帧固定前进frame时长，与实际运行速度无关
+pub struct FrameClock {
+    now: Duration,
+    frame: Duration,
+}
+
+impl FrameClock {
+    pub fn new(frame: Duration) -> Self {
+        Self {
+            now: Duration::ZERO,
+            frame,
+        }
+    }
+}
+
+impl Clock for FrameClock {
+    fn tick(&mut self) -> Duration {
+        self.now += self.frame;
+        self.now
+    }
+}
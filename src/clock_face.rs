@@ -0,0 +1,158 @@
+// 时钟: 把当前时间绘制成蒙版，落在数字笔画里的格子更亮或者停留不消失，让代码雨组成时间
+use std::{fmt::Write, time::Duration};
+
+use chrono::{DateTime, Local};
+use font_kit::font::Font;
+use raqote::{Color, DrawOptions, DrawTarget, Point, SolidSource, Source};
+
+use crate::{
+    config::{default_font, has_glyphs, load_font, ClockConfig},
+    grid::Grid,
+};
+
+pub struct ClockFace {
+    config: ClockConfig,
+    //模拟时钟开始时的时间，之后按模拟时钟推进
+    start: DateTime<Local>,
+    //查找字符时依次使用的字体序号(同Config::fallback_chain)
+    chain: Vec<usize>,
+    //所有字体中都没有数字时使用默认字体
+    fallback: Option<Font>,
+    //上次绘制的文字，每秒检查一次是否改变
+    text: String,
+    mask: DrawTarget,
+    //文字像素区域在蒙版中的位置和大小
+    bounds: (i32, i32, i32, i32),
+}
+
+impl ClockFace {
+    /// 没有开启时返回None，chain为Config::fallback_chain()
+    pub fn new(config: &ClockConfig, chain: Vec<usize>) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let mut config = config.clone();
+        let start = Local::now();
+        //时间格式错误时使用默认格式
+        if format_time(start, &config.format).is_none() {
+            eprintln!("时间格式错误:{}", config.format);
+            config.format = ClockConfig::default().format;
+        }
+        Some(Self {
+            config,
+            start,
+            chain,
+            fallback: load_font(&default_font()).ok(),
+            text: String::new(),
+            mask: DrawTarget::new(1, 1),
+            bounds: (0, 0, 0, 0),
+        })
+    }
+
+    /// 渲染大小改变后需要重新计算
+    pub fn reset(&mut self) {
+        self.text.clear();
+    }
+
+    /// now: 模拟时钟的当前时间 fonts: Config::fonts()对应的字体
+    /// 时间文字改变时重新绘制蒙版，返回是否改变
+    pub fn update(&mut self, now: Duration, fonts: &[Font]) -> bool {
+        let time = self.start
+            + chrono::Duration::from_std(now).unwrap_or_else(|_| chrono::Duration::zero());
+        let text = format_time(time, &self.config.format).unwrap_or_default();
+        if text == self.text {
+            return false;
+        }
+        let size = self.config.font_size.max(1) as f32;
+        let (width, height) = (
+            (size * (text.chars().count() + 1) as f32) as i32,
+            (size * 2.0) as i32,
+        );
+        self.mask = DrawTarget::new(width, height);
+        self.mask
+            .clear(SolidSource::from_unpremultiplied_argb(0, 0, 0, 0));
+        //每个字符使用备用字体中第一个有该字符的字体
+        let mut x = size / 2.0;
+        for c in text.chars() {
+            let tile = c.to_string();
+            let font = self
+                .chain
+                .iter()
+                .filter_map(|i| fonts.get(*i))
+                .chain(self.fallback.iter())
+                .find(|f| has_glyphs(f, &tile));
+            let font = match font {
+                Some(font) => font,
+                None => {
+                    x += size / 2.0;
+                    continue;
+                }
+            };
+            self.mask.draw_text(
+                font,
+                size,
+                &tile,
+                Point::new(x, size * 1.5),
+                &Source::from(Color::new(255, 255, 255, 255)),
+                &DrawOptions::new(),
+            );
+            let advance = font
+                .glyph_for_char(c)
+                .and_then(|id| font.advance(id).ok())
+                .map(|a| a.x() / font.metrics().units_per_em as f32 * size);
+            x += advance.unwrap_or(size / 2.0);
+        }
+
+        //有像素的区域
+        let data = self.mask.get_data();
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, -1, -1);
+        for y in 0..height {
+            for x in 0..width {
+                if data[(y * width + x) as usize] >> 24 > 0 {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+        self.bounds = (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1);
+        self.text = text;
+        true
+    }
+
+    /// 网格每个格子在数字笔画中的程度 0.0~1.0
+    pub fn highlight(&self, grid: &Grid, width: u32, height: u32) -> Vec<f32> {
+        let (bx, by, bw, bh) = self.bounds;
+        //文字中心放在(x, y)比例的位置，不超出画面
+        let left = (width as f32 * self.config.x - bw as f32 / 2.0)
+            .clamp(0.0, (width as f32 - bw as f32).max(0.0));
+        let top = (height as f32 * self.config.y - bh as f32 / 2.0)
+            .clamp(0.0, (height as f32 - bh as f32).max(0.0));
+        let data = self.mask.get_data();
+        let mask_width = self.mask.width();
+
+        let mut highlight = Vec::with_capacity(grid.columns * grid.rows);
+        for row in 0..grid.rows {
+            for col in 0..grid.columns {
+                //格子中心(字符绘制在基线上方)
+                let x = ((col as f32 + 0.5) * grid.cell_width - left) as i32;
+                let y = (row as f32 * grid.cell_height - grid.font_size / 2.0 - top) as i32;
+                highlight.push(if x < 0 || y < 0 || x >= bw || y >= bh {
+                    0.0
+                } else {
+                    let p = data[((y + by) * mask_width + x + bx) as usize];
+                    (p >> 24) as f32 / 255.0
+                });
+            }
+        }
+        highlight
+    }
+}
+
+/// 按格式输出时间，格式错误时返回None(to_string()会panic)
+fn format_time(time: DateTime<Local>, format: &str) -> Option<String> {
+    let mut text = String::new();
+    write!(text, "{}", time.format(format)).ok()?;
+    Some(text)
+}
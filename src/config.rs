@@ -1,16 +1,23 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use app_dirs::{AppDataType, AppInfo};
-use font_kit::font::Font;
+use font_kit::{
+    family_name::FamilyName,
+    file_type::FileType,
+    font::Font,
+    properties::{Properties, Style, Weight},
+    source::SystemSource,
+};
 use once_cell::sync::Lazy;
 use raqote::Color;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
     vec,
 };
+use unicode_segmentation::UnicodeSegmentation;
 
 // 凤凰点阵体
 const FONT_VONWAON: &[u8] = include_bytes!("../fonts/VonwaonBitmap-16px.ttf");
@@ -18,6 +25,8 @@ const FONT_VONWAON: &[u8] = include_bytes!("../fonts/VonwaonBitmap-16px.ttf");
 const FONT_XIAO_ZHUAN: &[u8] = include_bytes!("../fonts/xiaozhuan.ttf");
 // 方正甲骨文
 const FONT_FZ_JIAGUWEN: &[u8] = include_bytes!("../fonts/FZJiaGuWen.ttf");
+// 无佛(只有"01无佛"四个字符)
+const FONT_WU_FO: &[u8] = include_bytes!("../fonts/无佛.ttf");
 
 pub const CHARACTERS_JAP: &str = r#"
     アイウエオカキクケコサシスセソタチツテトナニヌ
@@ -64,6 +73,61 @@ pub const CHARACTERS_ZHUANTI: &str = r#"
 心月支分丰乏丹予丑勿允互井云匹凶介仇仆仁仍升午友屯夫巨尺巴幻尤孔父斤木牛欠犬氏瓦
 牙止爪且世主包北加出代半去平布市叫可史只它四外本民必正白立目生石示用乎丘丙占刊兄
 印功令付仔失央巧左句古司台右召宁奴犯尼扔汁圣幼冬孕末未旦永甘瓜禾矛母皮甲申田穴玉"#;
+pub const CHARACTERS_WU_FO: &str = "01无佛";
+
+/// 内置字体
+pub struct BuiltinFont {
+    /// 保存在配置文件中的id，发布后不能修改
+    pub id: &'static str,
+    /// 设置界面中显示的名称
+    pub name: &'static str,
+    pub data: &'static [u8],
+    /// 选择字体时使用的字符，None表示保留当前字符
+    pub characters: Option<&'static str>,
+}
+
+/// 所有内置字体，第一个为默认字体
+pub const BUILTIN_FONTS: &[BuiltinFont] = &[
+    BuiltinFont {
+        id: "1",
+        name: "默认",
+        data: FONT_VONWAON,
+        characters: None,
+    },
+    BuiltinFont {
+        id: "2",
+        name: "小篆",
+        data: FONT_XIAO_ZHUAN,
+        characters: Some(CHARACTERS_ZHUANTI),
+    },
+    BuiltinFont {
+        id: "3",
+        name: "甲骨文",
+        data: FONT_FZ_JIAGUWEN,
+        characters: Some(CHARACTERS_JIAGUWEN),
+    },
+    BuiltinFont {
+        id: "4",
+        name: "永无BUG",
+        data: FONT_WU_FO,
+        characters: Some(CHARACTERS_WU_FO),
+    },
+];
+
+/// 按id查找内置字体
+pub fn builtin_font(id: &str) -> Option<&'static BuiltinFont> {
+    BUILTIN_FONTS.iter().find(|f| f.id == id)
+}
+
+/// 按显示名称查找内置字体
+pub fn builtin_font_by_name(name: &str) -> Option<&'static BuiltinFont> {
+    BUILTIN_FONTS.iter().find(|f| f.name == name)
+}
+
+/// 默认字体的id
+pub fn default_font() -> String {
+    BUILTIN_FONTS[0].id.to_string()
+}
 
 pub const APP_DATA_TYPE: AppDataType = AppDataType::UserConfig;
 
@@ -158,29 +222,144 @@ pub fn read_config_file() -> Option<Config> {
     cfg
 }
 
-/// 字体 "1"->凤凰点阵体 "2"->小篆 "3"->甲骨文 "字体文件名.ttf"->自定义ttf文件
-pub fn load_font(cfg: &Config) -> Result<Font> {
-    let font_name = cfg.font.clone();
+/// 把字符串拆分成字素簇，国旗、带修饰的emoji、带元音符号的天城文等都作为一个字符，去掉空白
+pub fn split_tiles(characters: &str) -> Vec<Arc<str>> {
+    characters
+        .graphemes(true)
+        .filter(|g| !g.trim().is_empty())
+        .map(Arc::from)
+        .collect()
+}
 
-    let bytes = if font_name == "2" {
-        FONT_XIAO_ZHUAN.to_vec()
-    } else if font_name == "3" {
-        FONT_FZ_JIAGUWEN.to_vec()
-    } else if font_name == "1" {
-        FONT_VONWAON.to_vec()
-    } else {
-        let mut f = File::open(font_name)?;
-        let mut bytes = vec![];
-        f.read_to_end(&mut bytes)?;
-        bytes
+/// 加载所有字体，第一个为font
+pub fn load_fonts(cfg: &Config) -> Result<Vec<Font>> {
+    cfg.fonts().iter().map(|f| load_font(f)).collect()
+}
+
+/// 零宽连接符和变体选择符，字体中通常没有这些码位，查找和绘制字形时忽略
+pub fn is_joiner(c: char) -> bool {
+    c == '\u{200d}' || ('\u{fe00}'..='\u{fe0f}').contains(&c)
+}
+
+/// 字体中是否有字符的所有码位(忽略零宽连接符和变体选择符)
+pub fn has_glyphs(font: &Font, tile: &str) -> bool {
+    tile.chars()
+        .filter(|c| !is_joiner(*c))
+        .all(|c| font.glyph_for_char(c).is_some_and(|id| id != 0))
+}
+
+//系统字体的前缀
+const SYSTEM_FONT: &str = "system:";
+
+/// 系统字体 "system:字体名称[:粗细][:样式]"，例如 "system:Noto Sans CJK SC:700:italic"
+pub fn system_font(family: &str) -> String {
+    format!("{}{}", SYSTEM_FONT, family)
+}
+
+/// font为系统字体时返回字体名称
+pub fn system_font_family(font: &str) -> Option<&str> {
+    font.strip_prefix(SYSTEM_FONT)
+        .map(|spec| spec.split(':').next().unwrap_or_default())
+}
+
+/// 系统中安装的所有字体名称
+pub fn system_font_families() -> Vec<String> {
+    let mut families = SystemSource::new().all_families().unwrap_or_default();
+    families.sort();
+    families.dedup();
+    families
+}
+
+/// 按名称、粗细(100~900或bold)和样式(italic、oblique)从系统中查找最接近的字体
+fn load_system_font(spec: &str) -> Result<Font> {
+    let mut parts = spec.split(':');
+    let family = parts.next().unwrap_or_default().to_string();
+    let mut properties = Properties::new();
+    for part in parts {
+        if let Ok(weight) = part.parse::<f32>() {
+            properties.weight(Weight(weight));
+        } else if part == "bold" {
+            properties.weight(Weight::BOLD);
+        } else if part == "italic" {
+            properties.style(Style::Italic);
+        } else if part == "oblique" {
+            properties.style(Style::Oblique);
+        }
+    }
+    let handle =
+        SystemSource::new().select_best_match(&[FamilyName::Title(family)], &properties)?;
+    Ok(handle.load()?)
+}
+
+/// 文件路径后面可以用#指定字体集中的字体，例如 "msyh.ttc#1" 或 "msyh.ttc#Microsoft YaHei UI"
+pub fn split_font_face(font: &str) -> (&str, Option<&str>) {
+    if Path::new(font).exists() {
+        return (font, None);
+    }
+    match font.rsplit_once('#') {
+        Some((path, face)) => (path, Some(face)),
+        None => (font, None),
+    }
+}
+
+/// 字体集中第index个字体
+pub fn font_face(path: &str, index: usize) -> String {
+    format!("{}#{}", path, index)
+}
+
+/// 字体文件中的字体数量，ttc、otc字体集中有多个
+fn face_count(bytes: &Arc<Vec<u8>>) -> Result<u32> {
+    Ok(match Font::analyze_bytes(bytes.clone())? {
+        FileType::Single => 1,
+        FileType::Collection(count) => count,
+    })
+}
+
+/// 字体文件中所有字体的名称，按在字体集中的顺序排列
+pub fn font_faces(path: &str) -> Result<Vec<String>> {
+    let bytes = Arc::new(fs::read(path)?);
+    (0..face_count(&bytes)?)
+        .map(|i| Ok(Font::from_bytes(bytes.clone(), i)?.full_name()))
+        .collect()
+}
+
+/// 按序号或名称(完整名称或PostScript名称)查找字体集中的字体
+fn face_index(bytes: &Arc<Vec<u8>>, face: &str) -> Result<u32> {
+    if let Ok(index) = face.parse() {
+        return Ok(index);
+    }
+    for i in 0..face_count(bytes)? {
+        let font = Font::from_bytes(bytes.clone(), i)?;
+        if font.full_name() == face || font.postscript_name().as_deref() == Some(face) {
+            return Ok(i);
+        }
+    }
+    Err(anyhow!("字体文件中没有{}", face))
+}
+
+/// 按font的取值加载字体: 内置字体id(见BUILTIN_FONTS) "system:字体名称"->系统字体 其他为字体文件路径(可带#字体序号或名称)
+pub fn load_font(font_name: &str) -> Result<Font> {
+    if let Some(spec) = font_name.strip_prefix(SYSTEM_FONT) {
+        return load_system_font(spec);
+    }
+    if let Some(font) = builtin_font(font_name) {
+        return Ok(Font::from_bytes(Arc::new(font.data.to_vec()), 0)?);
+    }
+    //ttf、otf或者ttc、otc字体集
+    let (path, face) = split_font_face(font_name);
+    let bytes = Arc::new(fs::read(path)?);
+    let index = match face {
+        Some(face) => face_index(&bytes, face)?,
+        None => 0,
     };
-    Ok(Font::from_bytes(Arc::new(bytes), 0)?)
+    Ok(Font::from_bytes(bytes, index)?)
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     characters: String,
-    /// 字体 "1"->凤凰点阵体 "2"->小篆 "3"->甲骨文 "字体文件名.ttf"->自定义ttf文件
+    /// 字体 "1"->凤凰点阵体 "2"->小篆 "3"->甲骨文 "4"->永无BUG "system:字体名称[:粗细][:样式]"->系统字体
+    /// "字体文件名.ttf"->自定义字体文件(ttf、otf、ttc、otc)，字体集可以用"文件名.ttc#序号"选择其中的字体
     pub font: String,
     pub font_size: i32,
     pub color: String,
@@ -195,13 +374,493 @@ pub struct Config {
     pub logical_size: u32,
     pub mutation_rate: f32,
     pub frame_delay: u64,
+    /// 随机种子，设置后相同的配置每次运行使用相同的随机数(导出帧时得到相同的画面)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// 平滑滚动: 头部字符逐像素移动，残影仍然对齐网格
+    #[serde(default)]
+    pub smooth: bool,
+    /// 下落方向 down | up | left_to_right | right_to_left | angled
+    #[serde(default)]
+    pub direction: Direction,
+    /// angled方向偏离竖直方向的角度(度)，正数向右倾斜
+    #[serde(default = "default_angle")]
+    pub angle: f32,
+    /// 备用字体，按顺序查找font中没有的字符，取值同font
+    #[serde(default)]
+    pub fallback_fonts: Vec<String>,
+    /// 残影淡出设置
+    #[serde(default)]
+    pub fade: FadeConfig,
+    /// 下落速度设置
+    #[serde(default)]
+    pub speed: SpeedConfig,
+    /// 字形变换设置
+    #[serde(default)]
+    pub glyph: GlyphConfig,
+    /// 辉光设置
+    #[serde(default)]
+    pub bloom: BloomConfig,
+    /// CRT显示器效果设置
+    #[serde(default)]
+    pub crt: CrtConfig,
+    /// 故障效果设置
+    #[serde(default)]
+    pub glitch: GlitchConfig,
+    /// 图片剪影设置
+    #[serde(default)]
+    pub silhouette: SilhouetteConfig,
+    /// 解码消息设置
+    #[serde(default)]
+    pub message: MessageConfig,
+    /// 时钟设置
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// 多种颜色、渐变和色相变化
+    #[serde(default)]
+    pub palette: PaletteConfig,
+    /// 景深层，从远到近排列，为空时只有一层(使用font_size)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<LayerConfig>,
+    /// 按权重混合的多个字符集，为空时只使用characters
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub charsets: Vec<CharsetConfig>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharsetConfig {
+    pub characters: String,
+    /// 权重，例如三个字符集分别为70、20、10
+    pub weight: f32,
+    /// 字体，取值同font，为空时使用font
+    pub font: String,
+    /// 本字符集的字形变换，不设置时使用glyph.transform
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<GlyphTransform>,
+}
+
+impl Default for CharsetConfig {
+    fn default() -> Self {
+        Self {
+            characters: String::new(),
+            weight: 1.0,
+            font: String::new(),
+            transform: None,
+        }
+    }
+}
+
+impl CharsetConfig {
+    /// 去掉空白后的字符
+    pub fn tiles(&self) -> Vec<Arc<str>> {
+        split_tiles(&self.characters)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerConfig {
+    pub font_size: i32,
+    /// 下落速度倍数
+    pub speed: f32,
+    /// 亮度 0.0~1.0，远处的层可以调暗
+    pub brightness: f32,
+    /// 模糊半径(像素)，0->不模糊
+    pub blur: u32,
+}
+
+impl Default for LayerConfig {
+    fn default() -> Self {
+        Self {
+            font_size: 12,
+            speed: 1.0,
+            brightness: 1.0,
+            blur: 0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BloomConfig {
+    pub enabled: bool,
+    /// 叠加强度，1.0->原样叠加
+    pub intensity: f32,
+    /// 模糊半径(渲染像素)
+    pub radius: u32,
+    /// 亮度阈值 0.0~1.0，超过的像素才会发光
+    pub threshold: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 1.0,
+            radius: 4,
+            threshold: 0.7,
+        }
+    }
+}
+
+/// 各项强度都是0.0~1.0，0->关闭该项
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrtConfig {
+    pub enabled: bool,
+    /// 扫描线变暗程度
+    pub scanlines: f32,
+    /// 荧光点阵(RGB条纹)强度
+    pub phosphor_mask: f32,
+    /// 桶形畸变程度
+    pub curvature: f32,
+    /// 暗角程度
+    pub vignette: f32,
+    /// 闪烁幅度
+    pub flicker: f32,
+}
+
+impl Default for CrtConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scanlines: 0.4,
+            phosphor_mask: 0.2,
+            curvature: 0.05,
+            vignette: 0.3,
+            flicker: 0.03,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlitchConfig {
+    /// 随机出现故障，关闭时仍然可以用F2键触发
+    pub enabled: bool,
+    /// 每分钟平均出现的次数
+    pub frequency: f32,
+    /// 强度 0.0~1.0
+    pub intensity: f32,
+    /// 每次持续的毫秒数
+    pub duration_ms: u64,
+}
+
+impl Default for GlitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: 2.0,
+            intensity: 0.5,
+            duration_ms: 200,
+        }
+    }
+}
+
+/// 剪影如何影响字符
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SilhouetteMode {
+    /// 只按图片亮度调整字符亮度
+    #[default]
+    Brightness,
+    /// 同时使用图片的颜色
+    Color,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilhouetteConfig {
+    /// PNG或JPEG图片路径，为空时不显示剪影
+    pub image: String,
+    pub mode: SilhouetteMode,
+    /// 图片黑色部分的字符亮度 0.0~1.0
+    pub dim: f32,
+}
+
+impl Default for SilhouetteConfig {
+    fn default() -> Self {
+        Self {
+            image: String::new(),
+            mode: SilhouetteMode::Brightness,
+            dim: 0.1,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageConfig {
+    /// 依次显示的消息，为空时不显示，可以用\n换行
+    pub messages: Vec<String>,
+    pub font_size: i32,
+    /// 两条消息之间只有代码雨的毫秒数
+    pub interval_ms: u64,
+    /// 从第一个字符落下到全部锁定的毫秒数
+    pub decode_ms: u64,
+    /// 全部锁定后停留的毫秒数
+    pub hold_ms: u64,
+    /// 解码中的字符每隔多少毫秒换一个随机字符
+    pub cycle_ms: u64,
+}
+
+impl Default for MessageConfig {
+    fn default() -> Self {
+        Self {
+            messages: vec![],
+            font_size: 24,
+            interval_ms: 10000,
+            decode_ms: 2000,
+            hold_ms: 4000,
+            cycle_ms: 60,
+        }
+    }
+}
+
+/// 数字笔画中的字符如何显示
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockMode {
+    /// 笔画中的字符更亮
+    #[default]
+    Bright,
+    /// 落到笔画中的字符停留不消失
+    Stop,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub enabled: bool,
+    /// 时间格式，例如 %H:%M:%S 或 %Y-%m-%d %H:%M
+    pub format: String,
+    /// 文字中心在画面中的位置(宽高的比例 0.0~1.0)
+    pub x: f32,
+    pub y: f32,
+    /// 文字大小(渲染像素)
+    pub font_size: i32,
+    pub mode: ClockMode,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: "%H:%M:%S".to_string(),
+            x: 0.5,
+            y: 0.5,
+            font_size: 120,
+            mode: ClockMode::Bright,
+        }
+    }
+}
+
+/// 调色板颜色的选择方式
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteMode {
+    /// 每列(通道)固定一种颜色
+    #[default]
+    Column,
+    /// 每次下落重新选择颜色
+    Drop,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaletteConfig {
+    /// 文字颜色列表(csscolorparser格式)，为空时使用color
+    pub colors: Vec<String>,
+    pub mode: PaletteMode,
+    /// 残影尾部的颜色，从头部的文字颜色渐变过去，为空时不渐变
+    pub tail_color: String,
+    /// 色相每秒旋转的角度，0->不旋转
+    pub hue_speed: f32,
+    /// 相邻两列的色相差(度)，与hue_speed一起形成流动的彩虹
+    pub hue_spread: f32,
+}
+
+/// 字形变换(镜像、翻转、旋转)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GlyphTransform {
+    #[default]
+    None,
+    /// 水平镜像
+    Mirror,
+    /// 上下翻转
+    Flip,
+    /// 顺时针旋转90度
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlyphConfig {
+    /// 字符使用的变换(字符集可以单独设置)，电影中的日文字符是水平镜像的
+    pub transform: GlyphTransform,
+    /// 随机变换的候选列表
+    pub random_transforms: Vec<GlyphTransform>,
+    /// 每个字符使用随机变换的概率 0.0~1.0
+    pub random_rate: f32,
+}
+
+impl Default for GlyphConfig {
+    fn default() -> Self {
+        Self {
+            transform: GlyphTransform::None,
+            random_transforms: vec![GlyphTransform::Mirror],
+            random_rate: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedConfig {
+    /// 最小下落速度(每秒前进的字符数)
+    pub min: f32,
+    /// 最大下落速度(每秒前进的字符数)
+    pub max: f32,
+    /// 下落过程中速度的最大变化量(每秒)，0->匀速
+    pub acceleration: f32,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        Self {
+            min: 20.0,
+            max: 20.0,
+            acceleration: 0.0,
+        }
+    }
+}
+
+impl SpeedConfig {
+    /// 速度范围，保证min<=max且大于0
+    pub fn range(&self) -> (f32, f32) {
+        let min = self.min.max(0.1);
+        (min, self.max.max(min))
+    }
+
+    /// 平均速度(每秒前进的字符数)
+    pub fn average(&self) -> f32 {
+        let (min, max) = self.range();
+        (min + max) / 2.0
+    }
+}
+
+/// 下落方向
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Down,
+    Up,
+    LeftToRight,
+    RightToLeft,
+    Angled,
+}
+
+fn default_angle() -> f32 {
+    20.0
+}
+
+/// 淡出曲线
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FadeCurve {
+    Linear,
+    Exponential,
+    Step,
+}
+
+impl FadeCurve {
+    /// t: 0.0(刚出现)~1.0(完全消失)，返回亮度 0.0~1.0
+    pub fn apply(&self, t: f32, steps: u32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => 1.0 - t,
+            //t=1时衰减到1/32
+            FadeCurve::Exponential => (t * (1.0f32 / 32.0).ln()).exp(),
+            FadeCurve::Step => {
+                let steps = steps.max(1) as f32;
+                1.0 - (t * steps).floor() / steps
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FadeConfig {
+    /// 残影淡出曲线 linear | exponential | step
+    pub curve: FadeCurve,
+    /// step曲线的级数
+    pub steps: u32,
+    /// 残影长度(字符数，按每个字符串自己的速度换算成时间)，0->根据fade_speed计算
+    pub trail_length: u32,
+    /// 残影持续时间(毫秒)，大于0时代替trail_length
+    pub trail_ms: u64,
+    /// 头部字符从闪光色过渡到文字色的曲线
+    pub head_curve: FadeCurve,
+    /// 头部闪光持续的字符数
+    pub head_length: u32,
+    /// 头部闪光持续时间(毫秒)，大于0时代替head_length
+    pub head_ms: u64,
+}
+
+impl Default for FadeConfig {
+    fn default() -> Self {
+        Self {
+            curve: FadeCurve::Exponential,
+            steps: 4,
+            trail_length: 0,
+            trail_ms: 0,
+            head_curve: FadeCurve::Linear,
+            head_length: 1,
+            head_ms: 0,
+        }
+    }
+}
+
+impl FadeConfig {
+    /// 残影持续的秒数，speed为每秒前进的字符数(按字符数设置长度时用来换算)
+    pub fn trail_time(&self, cfg: &Config, speed: f32) -> f32 {
+        if self.trail_ms > 0 {
+            self.trail_ms as f32 / 1000.0
+        } else if self.trail_length > 0 {
+            self.trail_length as f32 / speed
+        } else {
+            //兼容旧配置: 每个字符亮度保留(1-fade_speed*2/255)，衰减到1/32所需的字符数
+            let alpha = (cfg.fade_speed * 2).clamp(1, 255) as f32 / 255.0;
+            let length = if alpha >= 1.0 {
+                1.0
+            } else {
+                ((1.0f32 / 32.0).ln() / (1.0 - alpha).ln()).ceil()
+            };
+            length / speed
+        }
+    }
+
+    /// 头部闪光持续的秒数
+    pub fn head_time(&self, speed: f32) -> f32 {
+        if self.head_ms > 0 {
+            self.head_ms as f32 / 1000.0
+        } else {
+            self.head_length.max(1) as f32 / speed
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             characters: "01".to_string(),
-            font: "1".to_string(),
+            font: default_font(),
             font_size: 12,
             color: "rgb(0, 255, 70)".to_string(),
             light_color: "white".to_string(),
@@ -221,6 +880,23 @@ impl Default for Config {
             frame_delay: 500,
             #[cfg(not(debug_assertions))]
             frame_delay: 50,
+            seed: None,
+            smooth: false,
+            direction: Direction::Down,
+            angle: default_angle(),
+            fallback_fonts: vec![],
+            fade: FadeConfig::default(),
+            speed: SpeedConfig::default(),
+            glyph: GlyphConfig::default(),
+            bloom: BloomConfig::default(),
+            crt: CrtConfig::default(),
+            glitch: GlitchConfig::default(),
+            silhouette: SilhouetteConfig::default(),
+            message: MessageConfig::default(),
+            clock: ClockConfig::default(),
+            palette: PaletteConfig::default(),
+            layers: vec![],
+            charsets: vec![],
         }
     }
 }
@@ -257,10 +933,88 @@ impl Config {
         self.characters = characters.to_string();
     }
 
+    /// 所有景深层(从远到近)
+    pub fn layers(&self) -> Vec<LayerConfig> {
+        if self.layers.is_empty() {
+            vec![LayerConfig {
+                font_size: self.font_size,
+                ..Default::default()
+            }]
+        } else {
+            self.layers.clone()
+        }
+    }
+
+    /// 所有字符集，为空时只有characters一个字符集
+    pub fn charsets(&self) -> Vec<CharsetConfig> {
+        if self.charsets.is_empty() {
+            vec![CharsetConfig {
+                characters: self.characters_plain(),
+                ..Default::default()
+            }]
+        } else {
+            self.charsets.clone()
+        }
+    }
+
+    /// 需要加载的字体，第一个为font，之后是备用字体和字符集中用到的其他字体
+    pub fn fonts(&self) -> Vec<String> {
+        let mut fonts = vec![self.font.clone()];
+        let others = self
+            .fallback_fonts
+            .iter()
+            .chain(self.charsets.iter().map(|set| &set.font));
+        for font in others {
+            if !font.is_empty() && !fonts.contains(font) {
+                fonts.push(font.clone());
+            }
+        }
+        fonts
+    }
+
+    /// 查找字符时依次使用的字体(font和备用字体)在fonts()中的序号
+    pub fn fallback_chain(&self) -> Vec<usize> {
+        let mut chain = vec![0];
+        for font in &self.fallback_fonts {
+            let index = self.font_index(font);
+            if !chain.contains(&index) {
+                chain.push(index);
+            }
+        }
+        chain
+    }
+
+    /// 字体在fonts()中的序号，为空时为0
+    pub fn font_index(&self, font: &str) -> usize {
+        self.fonts().iter().position(|f| f == font).unwrap_or(0)
+    }
+
     pub fn color(&self) -> Color {
         Self::parse_color(&self.color, csscolorparser::Color::from_rgb_u8(0, 255, 70))
     }
 
+    /// 调色板中的颜色，无法解析的使用color
+    pub fn palette(&self) -> Vec<Color> {
+        let default = csscolorparser::parse(&self.color)
+            .unwrap_or_else(|_| csscolorparser::Color::from_rgb_u8(0, 255, 70));
+        self.palette
+            .colors
+            .iter()
+            .map(|c| Self::parse_color(c, default.clone()))
+            .collect()
+    }
+
+    pub fn tail_color(&self) -> Option<Color> {
+        if self.palette.tail_color.is_empty() {
+            None
+        } else {
+            Some(Self::parse_color(
+                &self.palette.tail_color,
+                csscolorparser::Color::from_rgb_u8(0, 0, 0),
+            ))
+        }
+    }
+
     pub fn light_color(&self) -> Color {
         Self::parse_color(
             &self.light_color,
@@ -0,0 +1,122 @@
+// CRT显示器效果: 扫描线、荧光点阵、桶形畸变、暗角和闪烁，在放大后的输出画面上处理
+use crate::config::CrtConfig;
+
+//畸变后超出屏幕的像素
+const OUTSIDE: u32 = u32::MAX;
+
+pub struct Crt {
+    config: CrtConfig,
+    //每个输出像素对应的源像素(桶形畸变)，没有畸变时为None
+    map: Option<Vec<u32>>,
+    //每列RGB三个通道的亮度系数(0~256): 荧光点阵和水平方向的暗角
+    columns: Vec<[u32; 3]>,
+    //每行的亮度系数(0~256): 扫描线和竖直方向的暗角
+    rows: Vec<u32>,
+    buffer: Vec<u32>,
+}
+
+impl Crt {
+    /// width/height为输出大小，scale为输出与渲染大小的比例(每条扫描线的高度)
+    pub fn new(config: CrtConfig, width: u32, height: u32, scale: f32) -> Self {
+        let (w, h) = (width as f32, height as f32);
+        //归一化到-1.0~1.0
+        let u = |x: u32| (x as f32 + 0.5) / w * 2.0 - 1.0;
+        let v = |y: u32| (y as f32 + 0.5) / h * 2.0 - 1.0;
+        let curvature = config.curvature.max(0.0);
+        let map = if curvature > 0.0 {
+            let mut map = Vec::with_capacity((width * height) as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    let (u, v) = (u(x), v(y));
+                    let k = 1.0 + curvature * (u * u + v * v);
+                    let (su, sv) = (u * k, v * k);
+                    map.push(if su.abs() > 1.0 || sv.abs() > 1.0 {
+                        OUTSIDE
+                    } else {
+                        let sx = (((su + 1.0) / 2.0 * w) as u32).min(width - 1);
+                        let sy = (((sv + 1.0) / 2.0 * h) as u32).min(height - 1);
+                        sy * width + sx
+                    });
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        //暗角按水平和竖直方向分开计算后相乘
+        let vignette = config.vignette.clamp(0.0, 1.0) / 2.0;
+        let to_shade = |c: f32| (c.max(0.0) * 256.0) as u32;
+        let columns = (0..width)
+            .map(|x| {
+                let base = 1.0 - vignette * u(x) * u(x);
+                //荧光点阵: 相邻三列分别突出R、G、B
+                let mut rgb = [base; 3];
+                for (i, c) in rgb.iter_mut().enumerate() {
+                    if x as usize % 3 != i {
+                        *c *= 1.0 - config.phosphor_mask.clamp(0.0, 1.0);
+                    }
+                }
+                rgb.map(to_shade)
+            })
+            .collect();
+        let period = (scale.round() as u32).max(2);
+        let rows = (0..height)
+            .map(|y| {
+                let mut base = 1.0 - vignette * v(y) * v(y);
+                //每个渲染像素行的最后一行变暗
+                if y % period == period - 1 {
+                    base *= 1.0 - config.scanlines.clamp(0.0, 1.0);
+                }
+                to_shade(base)
+            })
+            .collect();
+        Self {
+            config,
+            map,
+            columns,
+            rows,
+            buffer: vec![],
+        }
+    }
+
+    /// time: 模拟时钟的秒数，用于计算闪烁
+    pub fn apply(&mut self, data: &mut [u32], time: f32) {
+        //有畸变时需要从原画面取像素
+        if self.map.is_some() {
+            self.buffer.clear();
+            self.buffer.extend_from_slice(data);
+        }
+        //两个不成倍数的频率叠加，闪烁看起来没有规律
+        let wave = ((time * 59.0).sin() + (time * 7.3).sin()) / 2.0;
+        let flicker =
+            ((1.0 - self.config.flicker.clamp(0.0, 1.0) * (0.5 + wave / 2.0)) * 256.0) as u32;
+        let width = self.columns.len();
+        if width == 0 {
+            return;
+        }
+        for (y, (line, row)) in data.chunks_mut(width).zip(&self.rows).enumerate() {
+            let row = (row * flicker) >> 8;
+            for (x, (d, shade)) in line.iter_mut().zip(&self.columns).enumerate() {
+                let p = match &self.map {
+                    None => *d,
+                    Some(map) => match map[y * width + x] {
+                        OUTSIDE => {
+                            *d = 0xff000000;
+                            continue;
+                        }
+                        src => self.buffer[src as usize],
+                    },
+                };
+                let scale = |shift: u32, s: u32| {
+                    let s = (s * row) >> 8;
+                    ((((p >> shift) & 0xff) * s) >> 8).min(255) << shift
+                };
+                *d = (p & 0xff000000)
+                    | scale(16, shade[0])
+                    | scale(8, shade[1])
+                    | scale(0, shade[2]);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// 故障效果: 偶尔在几帧内错开水平条带、分离RGB通道并打乱字符块
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raqote::DrawTarget;
+
+use crate::config::GlitchConfig;
+
+pub struct Glitch {
+    config: GlitchConfig,
+    rng: StdRng,
+    //字符块的边长(像素)
+    cell: usize,
+    //故障持续到的时间
+    until: Duration,
+    //等待下一帧开始故障(快捷键触发)
+    pending: bool,
+    buffer: Vec<u32>,
+}
+
+impl Glitch {
+    pub fn new(config: GlitchConfig, cell: usize, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            cell: cell.max(1),
+            until: Duration::ZERO,
+            pending: false,
+            buffer: vec![],
+        }
+    }
+
+    /// 立即开始一次故障
+    pub fn trigger(&mut self) {
+        self.pending = true;
+    }
+
+    /// now: 模拟时钟的当前时间 dt: 距上一帧的秒数
+    pub fn apply(&mut self, canvas: &mut DrawTarget, now: Duration, dt: f32) {
+        //frequency为每分钟平均次数
+        let chance = 1.0 - (-self.config.frequency.max(0.0) / 60.0 * dt).exp();
+        if now >= self.until
+            && (self.pending || (self.config.enabled && self.rng.gen::<f32>() < chance))
+        {
+            self.until = now + Duration::from_millis(self.config.duration_ms);
+            //正在显示时按下的F2等本次结束后再开始
+            self.pending = false;
+        }
+        if now >= self.until {
+            return;
+        }
+
+        let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let intensity = self.config.intensity.clamp(0.0, 1.0);
+        let data = canvas.get_data_mut();
+        self.buffer.clear();
+        self.buffer.extend_from_slice(data);
+
+        //RGB分离: 红色向左、蓝色向右取像素
+        let split = (intensity * 8.0) as usize;
+        if split > 0 {
+            for y in 0..height {
+                let row = y * width;
+                for x in 0..width {
+                    let r = self.buffer[row + x.saturating_sub(split)];
+                    let b = self.buffer[row + (x + split).min(width - 1)];
+                    let p = &mut data[row + x];
+                    *p = (*p & 0xff00ff00) | (r & 0x00ff0000) | (b & 0x000000ff);
+                }
+            }
+        }
+
+        //水平条带错位(循环移动)
+        let bands = 1 + (intensity * 6.0) as usize;
+        let max_offset = ((width as f32 * intensity / 10.0) as isize).max(1);
+        for _ in 0..bands {
+            let band_height = self.rng.gen_range(2..(height / 10).max(3));
+            let top = self.rng.gen_range(0..height);
+            let offset = self.rng.gen_range(-max_offset..=max_offset);
+            for y in top..(top + band_height).min(height) {
+                let row = &mut data[y * width..(y + 1) * width];
+                if offset > 0 {
+                    row.rotate_right(offset as usize % width);
+                } else {
+                    row.rotate_left(-offset as usize % width);
+                }
+            }
+        }
+
+        //把随机位置的字符块复制到另一个位置
+        let blocks = (intensity * 8.0) as usize;
+        for _ in 0..blocks {
+            let size = self.cell * self.rng.gen_range(1..=3);
+            if size >= width || size >= height {
+                break;
+            }
+            let (sx, sy) = (
+                self.rng.gen_range(0..width - size),
+                self.rng.gen_range(0..height - size),
+            );
+            let (dx, dy) = (
+                self.rng.gen_range(0..width - size),
+                self.rng.gen_range(0..height - size),
+            );
+            for y in 0..size {
+                let src = (sy + y) * width + sx;
+                let dst = (dy + y) * width + dx;
+                data[dst..dst + size].copy_from_slice(&self.buffer[src..src + size]);
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+// 字符网格: 记录屏幕上每个格子当前的字符、亮度和存在时间，每帧根据网格重绘整个画面
+use raqote::{Color, DrawTarget, Point};
+
+use crate::{
+    atlas::{Glyph, GlyphAtlas},
+    config::{ClockMode, Config, Direction, FadeCurve, LayerConfig},
+    silhouette::Tint,
+};
+
+#[derive(Clone, Default)]
+pub struct Cell {
+    //当前字符，None表示空格子
+    pub glyph: Option<Glyph>,
+    //调色板中的颜色，None表示使用文字颜色
+    pub color: Option<Color>,
+    //亮度 0.0~1.0
+    pub brightness: f32,
+    //字符出现后经过的秒数，0表示刚落下的头部字符
+    pub age: f32,
+    //本格残影和头部闪光持续的秒数(按写入字符串的速度换算)
+    pub trail_time: f32,
+    pub head_time: f32,
+}
+
+impl Cell {
+    pub fn is_empty(&self) -> bool {
+        self.glyph.is_none()
+    }
+}
+
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+    pub direction: Direction,
+    //angled方向每前进一行横向移动的列数
+    pub slope: f32,
+    pub font_size: f32,
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub color: Color,
+    pub light_color: Color,
+    //残影尾部颜色
+    pub tail_color: Option<Color>,
+    //当前色相偏移(度)
+    pub hue: f32,
+    pub hue_speed: f32,
+    pub hue_spread: f32,
+    //所在景深层的亮度
+    pub brightness: f32,
+    pub fade_curve: FadeCurve,
+    pub fade_steps: u32,
+    //按本层平均速度换算的残影持续秒数
+    pub trail_time: f32,
+    pub head_curve: FadeCurve,
+    //按本层平均速度换算的头部闪光持续秒数
+    pub head_time: f32,
+    //本层的平均速度
+    pub speed: f32,
+    //残影和头部闪光按字符数设置(持续时间随字符串的速度变化)
+    pub trail_by_length: bool,
+    pub head_by_length: bool,
+    //每个格子的剪影亮度和颜色，为空时不使用剪影
+    pub tints: Vec<Tint>,
+    //每个格子在时钟数字笔画中的程度，为空时不显示时钟
+    pub highlight: Vec<f32>,
+    pub clock_mode: ClockMode,
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    pub fn new(cfg: &Config, layer: &LayerConfig, width: u32, height: u32) -> Self {
+        let font_size = layer.font_size.max(1);
+        //本层的平均速度，用于把按字符数设置的残影长度换算成时间
+        let speed = cfg.speed.average() * layer.speed.max(0.01);
+        let mut grid = Self {
+            columns: 0,
+            rows: 0,
+            direction: cfg.direction,
+            slope: cfg.angle.clamp(-80.0, 80.0).to_radians().tan(),
+            font_size: font_size as f32,
+            cell_width: font_size as f32,
+            cell_height: (font_size as u32 + cfg.spaceing) as f32,
+            color: cfg.color(),
+            light_color: cfg.light_color(),
+            tail_color: cfg.tail_color(),
+            hue: 0.0,
+            hue_speed: cfg.palette.hue_speed,
+            hue_spread: cfg.palette.hue_spread,
+            brightness: layer.brightness.clamp(0.0, 1.0),
+            fade_curve: cfg.fade.curve,
+            fade_steps: cfg.fade.steps,
+            trail_time: cfg.fade.trail_time(cfg, speed),
+            head_curve: cfg.fade.head_curve,
+            head_time: cfg.fade.head_time(speed),
+            speed,
+            trail_by_length: cfg.fade.trail_ms == 0,
+            head_by_length: cfg.fade.head_ms == 0,
+            tints: vec![],
+            highlight: vec![],
+            clock_mode: cfg.clock.mode,
+            cells: vec![],
+        };
+        grid.resize(width, height);
+        grid
+    }
+
+    /// 根据新的渲染大小调整行列数，保留重叠区域内的格子
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let columns = (width as f32 / self.cell_width) as usize;
+        let rows = (height as f32 / self.cell_height) as usize + 1;
+        let mut cells = vec![Cell::default(); columns * rows];
+        for row in 0..rows.min(self.rows) {
+            for col in 0..columns.min(self.columns) {
+                cells[row * columns + col] = self.cells[row * self.columns + col].clone();
+            }
+        }
+        self.columns = columns;
+        self.rows = rows;
+        self.cells = cells;
+    }
+
+    /// 字符串的通道数(竖直方向为列数，水平方向为行数)
+    pub fn lanes(&self) -> usize {
+        match self.direction {
+            Direction::Down | Direction::Up => self.columns,
+            Direction::LeftToRight | Direction::RightToLeft => self.rows,
+            //倾斜时需要额外的通道覆盖从侧面进入屏幕的部分
+            Direction::Angled => self.columns + self.angled_extra(),
+        }
+    }
+
+    /// 每个通道的长度
+    pub fn lane_len(&self) -> usize {
+        match self.direction {
+            Direction::LeftToRight | Direction::RightToLeft => self.columns,
+            _ => self.rows,
+        }
+    }
+
+    fn angled_extra(&self) -> usize {
+        (self.rows as f32 * self.slope.abs()).ceil() as usize
+    }
+
+    /// 通道上第position个位置(可以是小数)对应的列和行(可能在屏幕外)
+    fn lane_position(&self, lane: usize, position: f32) -> (f32, f32) {
+        let lane = lane as f32;
+        match self.direction {
+            Direction::Down => (lane, position),
+            Direction::Up => (lane, self.rows as f32 - 1.0 - position),
+            Direction::LeftToRight => (position, lane),
+            Direction::RightToLeft => (self.columns as f32 - 1.0 - position, lane),
+            Direction::Angled => {
+                let offset = if self.slope > 0.0 {
+                    self.angled_extra() as f32
+                } else {
+                    0.0
+                };
+                (lane - offset + position * self.slope, position)
+            }
+        }
+    }
+
+    /// 通道上第index个格子的列和行，在屏幕外时返回None
+    pub fn lane_cell(&self, lane: usize, index: usize) -> Option<(usize, usize)> {
+        let (col, row) = self.lane_position(lane, index as f32);
+        let (col, row) = (col.round(), row.round());
+        if col < 0.0 || row < 0.0 || col >= self.columns as f32 || row >= self.rows as f32 {
+            None
+        } else {
+            Some((col as usize, row as usize))
+        }
+    }
+
+    /// 通道上第position个位置的绘制坐标(基线原点)
+    pub fn lane_point(&self, lane: usize, position: f32) -> Point {
+        let (col, row) = self.lane_position(lane, position);
+        Point::new(col * self.cell_width, row * self.cell_height)
+    }
+
+    pub fn get_mut(&mut self, col: usize, row: usize) -> Option<&mut Cell> {
+        if col < self.columns && row < self.rows {
+            self.cells.get_mut(row * self.columns + col)
+        } else {
+            None
+        }
+    }
+
+    /// 在格子中放入新字符，speed为写入字符的字符串当前的速度
+    pub fn set(&mut self, col: usize, row: usize, glyph: Glyph, color: Option<Color>, speed: f32) {
+        //按字符数设置的长度，速度越慢持续时间越长
+        let scale = if speed > 0.0 { self.speed / speed } else { 1.0 };
+        let scaled = |time: f32, by_length: bool| if by_length { time * scale } else { time };
+        let trail_time = scaled(self.trail_time, self.trail_by_length);
+        let head_time = scaled(self.head_time, self.head_by_length);
+        if let Some(cell) = self.get_mut(col, row) {
+            *cell = Cell {
+                glyph: Some(glyph),
+                color,
+                brightness: 1.0,
+                age: 0.0,
+                trail_time,
+                head_time,
+            };
+        }
+    }
+
+    /// 所有字符按经过的时间(秒)衰减
+    pub fn update(&mut self, dt: f32) {
+        self.hue = (self.hue + self.hue_speed * dt) % 360.0;
+        for (i, cell) in self.cells.iter_mut().enumerate() {
+            if cell.is_empty() {
+                continue;
+            }
+            cell.age += dt;
+            //落在时钟笔画中的字符不消失
+            if self.clock_mode == ClockMode::Stop && self.highlight.get(i).is_some_and(|h| *h > 0.5)
+            {
+                cell.brightness = 1.0;
+                continue;
+            }
+            let t = cell.age / cell.trail_time;
+            if t >= 1.0 {
+                *cell = Cell::default();
+            } else {
+                cell.brightness = self.fade_curve.apply(t, self.fade_steps);
+            }
+        }
+    }
+
+    /// 格子的绘制颜色: 头部从闪光色过渡到文字色，再乘以亮度
+    fn cell_color(&self, index: usize, cell: &Cell) -> Color {
+        let mut light = self
+            .head_curve
+            .apply(cell.age / cell.head_time, self.fade_steps);
+        let mut brightness = cell.brightness;
+        if self.clock_mode == ClockMode::Bright {
+            //时钟笔画中的残影保持明亮
+            let h = self.highlight.get(index).copied().unwrap_or(0.0);
+            brightness += (1.0 - brightness) * h;
+            light = light.max(h * 0.5);
+        }
+        let tint = self.tints.get(index);
+        let mut color = tint
+            .and_then(|t| t.color)
+            .or(cell.color)
+            .unwrap_or(self.color);
+        if let Some(tail) = self.tail_color {
+            //从头部到尾部渐变
+            color = mix(color, tail, 1.0 - (cell.age / cell.trail_time).min(1.0));
+        }
+        if self.hue_speed != 0.0 || self.hue_spread != 0.0 {
+            let col = index % self.columns.max(1);
+            color = rotate_hue(color, self.hue + col as f32 * self.hue_spread);
+        }
+        let c = mix(self.light_color, color, light);
+        let tint_brightness = tint.map_or(1.0, |t| t.brightness);
+        let alpha = c.a() as f32 * brightness * self.brightness * tint_brightness;
+        Color::new(alpha as u8, c.r(), c.g(), c.b())
+    }
+
+    /// 平滑滚动时绘制头部字符，position为通道上的位置(可以是小数)
+    pub fn draw_head(
+        &self,
+        canvas: &mut DrawTarget,
+        atlas: &mut GlyphAtlas,
+        lane: usize,
+        glyph: &Glyph,
+        position: f32,
+    ) {
+        //头部所在格子的剪影亮度
+        let tint_brightness = self
+            .lane_cell(lane, position.max(0.0).round() as usize)
+            .and_then(|(col, row)| self.tints.get(row * self.columns + col))
+            .map_or(1.0, |t| t.brightness);
+        let c = self.light_color;
+        let alpha = c.a() as f32 * self.brightness * tint_brightness;
+        let color = Color::new(alpha as u8, c.r(), c.g(), c.b());
+        atlas.draw(
+            canvas,
+            glyph,
+            self.font_size,
+            self.lane_point(lane, position),
+            color,
+        );
+    }
+
+    /// 绘制所有格子，返回绘制的字符数
+    pub fn draw(&self, canvas: &mut DrawTarget, atlas: &mut GlyphAtlas) -> i32 {
+        let mut count = 0;
+        for row in 0..self.rows {
+            for col in 0..self.columns {
+                let index = row * self.columns + col;
+                let cell = &self.cells[index];
+                let glyph = match &cell.glyph {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let color = self.cell_color(index, cell);
+                atlas.draw(
+                    canvas,
+                    glyph,
+                    self.font_size,
+                    Point::new(col as f32 * self.cell_width, row as f32 * self.cell_height),
+                    color,
+                );
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// 旋转颜色的色相(度)
+fn rotate_hue(c: Color, degrees: f32) -> Color {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (r, g, b) = (c.r() as f32, c.g() as f32, c.b() as f32);
+    let channel = |kr: f32, kg: f32, kb: f32| (r * kr + g * kg + b * kb).clamp(0.0, 255.0) as u8;
+    Color::new(
+        c.a(),
+        channel(
+            0.213 + 0.787 * cos - 0.213 * sin,
+            0.715 - 0.715 * cos - 0.715 * sin,
+            0.072 - 0.072 * cos + 0.928 * sin,
+        ),
+        channel(
+            0.213 - 0.213 * cos + 0.143 * sin,
+            0.715 + 0.285 * cos + 0.140 * sin,
+            0.072 - 0.072 * cos - 0.283 * sin,
+        ),
+        channel(
+            0.213 - 0.213 * cos - 0.787 * sin,
+            0.715 - 0.715 * cos + 0.715 * sin,
+            0.072 + 0.928 * cos + 0.072 * sin,
+        ),
+    )
+}
+
+/// 按比例t混合两种颜色，t=1时为a
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    let m = |x: u8, y: u8| (x as f32 * t + y as f32 * (1.0 - t)) as u8;
+    Color::new(
+        m(a.a(), b.a()),
+        m(a.r(), b.r()),
+        m(a.g(), b.g()),
+        m(a.b(), b.b()),
+    )
+}
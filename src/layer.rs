@@ -0,0 +1,149 @@
+// 景深层: 每层有独立的网格、字体大小、速度、亮度和模糊，从远到近绘制到同一个DrawTarget
+use std::time::Duration;
+
+use rand::Rng;
+use raqote::{DrawOptions, DrawTarget, Image, SolidSource};
+
+use crate::{
+    atlas::GlyphAtlas,
+    blur::box_blur,
+    characters::{self, CharacterString, Charsets},
+    config::{Config, LayerConfig},
+    grid::Grid,
+    silhouette::Silhouette,
+};
+
+pub struct Layer {
+    pub config: LayerConfig,
+    pub grid: Grid,
+    pub strings: Vec<CharacterString>,
+    //本层的随机种子
+    seed: u64,
+    //需要模糊的层先绘制到单独的画布
+    canvas: Option<DrawTarget>,
+}
+
+impl Layer {
+    pub fn new(
+        cfg: &Config,
+        layer: LayerConfig,
+        width: u32,
+        height: u32,
+        seed: u64,
+        silhouette: Option<&Silhouette>,
+        charsets: &Charsets,
+    ) -> Self {
+        let mut grid = Grid::new(cfg, &layer, width, height);
+        if let Some(s) = silhouette {
+            grid.tints = s.tints(&grid, width, height);
+        }
+        let strings = characters::init(cfg, &layer, &grid, seed, charsets);
+        let canvas = Self::create_canvas(&layer, width, height);
+        Self {
+            config: layer,
+            grid,
+            strings,
+            seed,
+            canvas,
+        }
+    }
+
+    fn create_canvas(layer: &LayerConfig, width: u32, height: u32) -> Option<DrawTarget> {
+        if layer.blur > 0 {
+            Some(DrawTarget::new(width as i32, height as i32))
+        } else {
+            None
+        }
+    }
+
+    /// 渲染大小改变，保留网格中已有的字符
+    pub fn resize(
+        &mut self,
+        cfg: &Config,
+        width: u32,
+        height: u32,
+        silhouette: Option<&Silhouette>,
+        charsets: &Charsets,
+    ) {
+        self.grid.resize(width, height);
+        if let Some(s) = silhouette {
+            self.grid.tints = s.tints(&self.grid, width, height);
+        }
+        characters::resize(
+            &mut self.strings,
+            cfg,
+            &self.config,
+            &self.grid,
+            self.seed,
+            charsets,
+        );
+        self.canvas = Self::create_canvas(&self.config, width, height);
+    }
+
+    /// now: 模拟时钟的当前时间 dt: 距上一帧的秒数
+    pub fn update(&mut self, now: Duration, dt: f32) {
+        self.grid.update(dt);
+        for st in &mut self.strings {
+            st.update(&mut self.grid, now, dt);
+        }
+    }
+
+    pub fn draw(&mut self, dt: &mut DrawTarget, atlas: &mut GlyphAtlas) -> i32 {
+        match self.canvas.as_mut() {
+            None => Self::draw_glyphs(&self.grid, &self.strings, dt, atlas),
+            Some(canvas) => {
+                canvas.clear(SolidSource::from_unpremultiplied_argb(0, 0, 0, 0));
+                let count = Self::draw_glyphs(&self.grid, &self.strings, canvas, atlas);
+                let (width, height) = (canvas.width(), canvas.height());
+                box_blur(
+                    canvas.get_data_mut(),
+                    width as usize,
+                    height as usize,
+                    self.config.blur as usize,
+                );
+                dt.draw_image_at(
+                    0.,
+                    0.,
+                    &Image {
+                        width,
+                        height,
+                        data: canvas.get_data(),
+                    },
+                    &DrawOptions::default(),
+                );
+                count
+            }
+        }
+    }
+
+    /// 绘制网格和平滑滚动的头部字符
+    fn draw_glyphs(
+        grid: &Grid,
+        strings: &[CharacterString],
+        canvas: &mut DrawTarget,
+        atlas: &mut GlyphAtlas,
+    ) -> i32 {
+        let mut count = grid.draw(canvas, atlas);
+        for st in strings {
+            if let Some((glyph, position)) = st.head() {
+                grid.draw_head(canvas, atlas, st.lane, glyph, position);
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+pub fn init(
+    cfg: &Config,
+    width: u32,
+    height: u32,
+    rng: &mut impl Rng,
+    silhouette: Option<&Silhouette>,
+    charsets: &Charsets,
+) -> Vec<Layer> {
+    cfg.layers()
+        .into_iter()
+        .map(|layer| Layer::new(cfg, layer, width, height, rng.gen(), silhouette, charsets))
+        .collect()
+}
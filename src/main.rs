@@ -2,9 +2,21 @@
 
 use anyhow::Result;
 mod app;
+mod atlas;
+mod bloom;
+mod blur;
 mod characters;
+mod clock;
+mod clock_face;
 mod config;
+mod crt;
+mod glitch;
+mod grid;
+mod layer;
+mod message;
+mod renderer;
 mod setting;
+mod silhouette;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> Result<()> {
@@ -13,7 +25,7 @@ fn main() -> Result<()> {
     use crate::setting::alert;
 
     let args: Vec<String> = env::args().collect();
-    for arg in &args[1..] {
+    for (i, arg) in args.iter().enumerate().skip(1) {
         let arg = arg.to_lowercase();
         if arg.starts_with("/p") {
             //收到 /p 参数, 结束
@@ -24,6 +36,15 @@ fn main() -> Result<()> {
             setting::open();
             return Ok(());
         }
+        if arg.starts_with("/e") {
+            //导出画面: /e 文件名.png [帧数]
+            let path = args.get(i + 1).map(|s| s.as_str()).unwrap_or("matrix.png");
+            let frames = args.get(i + 2).and_then(|s| s.parse().ok()).unwrap_or(100);
+            if let Err(err) = renderer::export_frame(config::read_config(), frames, path) {
+                eprintln!("导出失败:{:?}", err);
+            }
+            return Ok(());
+        }
     }
 
     //启动屏保
@@ -0,0 +1,236 @@
+// 解码消息: 代码雨中间定时出现消息，每个字符落下后在随机字符间变换，最后锁定为消息中的字符
+use std::{sync::Arc, time::Duration};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raqote::{Color, DrawOptions, DrawTarget, Point, SolidSource, Source};
+use unicode_segmentation::UnicodeSegmentation;
+
+use font_kit::font::Font;
+
+use crate::{
+    atlas::{Glyph, GlyphAtlas},
+    characters::{find_font, Charsets},
+    config::{Config, GlyphTransform, MessageConfig},
+};
+
+//字符落到位置上的秒数
+const DROP: f32 = 0.3;
+//字符落下的高度(字符数)
+const DROP_HEIGHT: f32 = 4.0;
+//停留结束后淡出的秒数
+const FADE: f32 = 1.0;
+
+struct Letter {
+    //消息中的字符，已经按备用字体选好字体
+    glyph: Glyph,
+    //当前显示的字符
+    current: Glyph,
+    line: usize,
+    col: usize,
+    //开始落下和锁定的时间(相对消息开始的秒数)
+    start: f32,
+    lock: f32,
+}
+
+pub struct Message {
+    config: MessageConfig,
+    rng: StdRng,
+    //解码时随机变换的字符，与代码雨相同
+    charsets: Charsets,
+    //查找消息字符时依次使用的字体序号(同Config::fallback_chain)
+    chain: Vec<usize>,
+    color: Color,
+    light_color: Color,
+    background: Color,
+    width: u32,
+    height: u32,
+    //下一条消息的序号
+    next: usize,
+    //本轮开始的时间(先等待interval_ms再显示消息)
+    start_time: Duration,
+    //上次更换随机字符的时间(相对消息开始的秒数)
+    cycle_time: f32,
+    letters: Vec<Letter>,
+    //每行的字符数
+    lines: Vec<usize>,
+}
+
+impl Message {
+    /// 没有设置消息时返回None
+    pub fn new(
+        cfg: &Config,
+        width: u32,
+        height: u32,
+        seed: u64,
+        charsets: &Charsets,
+    ) -> Option<Self> {
+        if cfg.message.messages.is_empty() {
+            return None;
+        }
+        Some(Self {
+            config: cfg.message.clone(),
+            rng: StdRng::seed_from_u64(seed),
+            charsets: charsets.clone(),
+            chain: cfg.fallback_chain(),
+            color: cfg.color(),
+            light_color: cfg.light_color(),
+            background: cfg.background(),
+            width,
+            height,
+            next: 0,
+            start_time: Duration::ZERO,
+            cycle_time: 0.0,
+            letters: vec![],
+            lines: vec![],
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    fn font_size(&self) -> f32 {
+        self.config.font_size.max(1) as f32
+    }
+
+    /// 开始显示下一条消息，每个字符的出现和锁定时间随机
+    /// fonts为Config::fonts()对应的字体，没有字体可以显示的字符留空
+    fn start_message(&mut self, fonts: &[Font]) {
+        let text = self.config.messages[self.next % self.config.messages.len()].clone();
+        self.next += 1;
+        let decode = self.config.decode_ms as f32 / 1000.0;
+        self.letters.clear();
+        self.lines.clear();
+        self.cycle_time = 0.0;
+        for (line, s) in text.lines().enumerate() {
+            //按字素簇排列，每个占一个字符的位置
+            let graphemes: Vec<&str> = s.graphemes(true).collect();
+            self.lines.push(graphemes.len());
+            for (col, tile) in graphemes.into_iter().enumerate() {
+                if tile.trim().is_empty() {
+                    continue;
+                }
+                let font = match find_font(fonts, &self.chain, tile) {
+                    Some(font) => font,
+                    None => continue,
+                };
+                let glyph = Glyph {
+                    tile: Arc::from(tile),
+                    font,
+                    transform: GlyphTransform::None,
+                };
+                let start = self.rng.gen_range(0.0..=decode / 2.0);
+                let lock = (start + DROP).max(self.rng.gen_range(decode / 2.0..=decode));
+                let current = self.charsets.random_glyph(&mut self.rng);
+                self.letters.push(Letter {
+                    glyph,
+                    current,
+                    line,
+                    col,
+                    start,
+                    lock,
+                });
+            }
+        }
+    }
+
+    /// 当前消息开始后经过的秒数，等待期间返回None
+    fn elapsed(&self, now: Duration) -> Option<f32> {
+        let t = now.saturating_sub(self.start_time).as_secs_f32();
+        let t = t - self.config.interval_ms as f32 / 1000.0;
+        if t < 0.0 {
+            None
+        } else {
+            Some(t)
+        }
+    }
+
+    /// fonts为Config::fonts()对应的字体
+    pub fn update(&mut self, now: Duration, fonts: &[Font]) {
+        let show = (self.config.decode_ms + self.config.hold_ms) as f32 / 1000.0;
+        let t = match self.elapsed(now) {
+            Some(t) => t,
+            None => return,
+        };
+        if self.letters.is_empty() && self.lines.is_empty() {
+            self.start_message(fonts);
+        }
+        if t >= show + FADE {
+            //显示结束，代码雨重新接管
+            self.start_time = now;
+            self.letters.clear();
+            self.lines.clear();
+            return;
+        }
+        //解码中的字符按固定的时间间隔更换，与帧率无关
+        let cycle = t - self.cycle_time >= self.config.cycle_ms as f32 / 1000.0;
+        if cycle {
+            self.cycle_time = t;
+        }
+        for i in 0..self.letters.len() {
+            let letter = &self.letters[i];
+            if t >= letter.lock {
+                self.letters[i].current = letter.glyph.clone();
+            } else if t >= letter.start && cycle {
+                self.letters[i].current = self.charsets.random_glyph(&mut self.rng);
+            }
+        }
+    }
+
+    /// 绘制消息，返回绘制的字符数
+    pub fn draw(&self, canvas: &mut DrawTarget, atlas: &mut GlyphAtlas, now: Duration) -> i32 {
+        let t = match self.elapsed(now) {
+            Some(t) => t,
+            None => return 0,
+        };
+        let show = (self.config.decode_ms + self.config.hold_ms) as f32 / 1000.0;
+        let alpha = (1.0 - (t - show) / FADE).clamp(0.0, 1.0);
+        let font_size = self.font_size();
+        let (width, height) = (self.width as f32, self.height as f32);
+        let top = height / 2.0 - self.lines.len() as f32 * font_size / 2.0;
+        let fade = |c: Color| Color::new((c.a() as f32 * alpha) as u8, c.r(), c.g(), c.b());
+
+        let mut count = 0;
+        for letter in &self.letters {
+            if t < letter.start {
+                continue;
+            }
+            let left = width / 2.0 - self.lines[letter.line] as f32 * font_size / 2.0;
+            let x = left + letter.col as f32 * font_size;
+            let y = top + (letter.line + 1) as f32 * font_size;
+            let drop = ((letter.start + DROP - t) / DROP).max(0.0) * DROP_HEIGHT * font_size;
+            if drop == 0.0 {
+                //落到位置后遮住后面的代码雨
+                let b = fade(self.background);
+                canvas.fill_rect(
+                    x,
+                    y - font_size,
+                    font_size,
+                    font_size,
+                    &Source::Solid(SolidSource::from_unpremultiplied_argb(
+                        b.a(),
+                        b.r(),
+                        b.g(),
+                        b.b(),
+                    )),
+                    &DrawOptions::new(),
+                );
+            }
+            let color = if t >= letter.lock {
+                self.light_color
+            } else {
+                self.color
+            };
+            atlas.draw(
+                canvas,
+                &letter.current,
+                font_size,
+                Point::new(x, y - drop),
+                fade(color),
+            );
+            count += 1;
+        }
+        count
+    }
+}
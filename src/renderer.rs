@@ -0,0 +1,347 @@
+// 渲染器: 按配置推进代码雨并输出画面，不依赖窗口，可用于预览、导出帧和测试
+use std::{num::NonZeroU32, time::Duration};
+
+use anyhow::{anyhow, Result};
+use fast_image_resize::{Image, PixelType, ResizeAlg, Resizer};
+use font_kit::font::Font;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raqote::{DrawTarget, SolidSource};
+
+use crate::{
+    atlas::GlyphAtlas,
+    bloom::Bloom,
+    characters::Charsets,
+    clock::{Clock, FrameClock, SystemClock},
+    clock_face::ClockFace,
+    config::{load_fonts, Config},
+    crt::Crt,
+    glitch::Glitch,
+    layer::{self, Layer},
+    message::Message,
+    silhouette::Silhouette,
+};
+
+//一帧最多推进的时间
+const MAX_STEP: Duration = Duration::from_millis(500);
+
+pub struct Renderer {
+    config: Config,
+    //输出大小
+    width: u32,
+    height: u32,
+    //按logical_size计算的渲染画布
+    dt: DrawTarget,
+    atlas: GlyphAtlas,
+    layers: Vec<Layer>,
+    //未设置图片时为None
+    silhouette: Option<Silhouette>,
+    //每个字符已经按备用字体选好字体
+    charsets: Charsets,
+    //没有设置消息时为None
+    message: Option<Message>,
+    //没有开启时为None
+    clock_face: Option<ClockFace>,
+    //未开启时为None
+    bloom: Option<Bloom>,
+    glitch: Glitch,
+    clock: Box<dyn Clock>,
+    //上一帧的时间
+    last_time: Duration,
+    background: SolidSource,
+    //缩放到输出大小
+    resizer: Resizer,
+    resized_image: Image<'static>,
+    output: DrawTarget,
+    //未开启时为None
+    crt: Option<Crt>,
+}
+
+impl Renderer {
+    /// 加载配置中的字体并创建渲染器，width/height为输出大小
+    pub fn new(config: Config, width: u32, height: u32) -> Result<Self> {
+        //加载字体耗时时间比较长
+        let fonts = load_fonts(&config)?;
+        Self::with_fonts(config, fonts, width, height)
+    }
+
+    /// fonts为config.fonts()对应的字体
+    pub fn with_fonts(config: Config, fonts: Vec<Font>, width: u32, height: u32) -> Result<Self> {
+        let (render_width, render_height) = render_size(&config, width, height);
+        //种子只决定随机数，需要可重复的画面时再用set_clock换成FrameClock
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        //图片加载失败时不显示剪影
+        let silhouette = Silhouette::load(&config.silhouette).unwrap_or_else(|err| {
+            eprintln!("剪影图片加载失败:{:?}", err);
+            None
+        });
+        let charsets = Charsets::load(&config, &fonts);
+        Ok(Self {
+            dt: DrawTarget::new(render_width as i32, render_height as i32),
+            //字形在第一次绘制时缓存
+            atlas: GlyphAtlas::new(fonts),
+            layers: layer::init(
+                &config,
+                render_width,
+                render_height,
+                &mut rng,
+                silhouette.as_ref(),
+                &charsets,
+            ),
+            silhouette,
+            bloom: if config.bloom.enabled {
+                Some(Bloom::new(config.bloom.clone()))
+            } else {
+                None
+            },
+            glitch: Glitch::new(config.glitch.clone(), glitch_block(&config), rng.gen()),
+            message: Message::new(&config, render_width, render_height, rng.gen(), &charsets),
+            charsets,
+            clock_face: ClockFace::new(&config.clock, config.fallback_chain()),
+            clock: Box::new(SystemClock::new()),
+            last_time: Duration::ZERO,
+            background: SolidSource::from(config.background()),
+            resizer: Resizer::new(ResizeAlg::Nearest),
+            resized_image: resized_image(width, height)?,
+            output: DrawTarget::new(width as i32, height as i32),
+            crt: create_crt(&config, width, height, render_width),
+            width,
+            height,
+            config,
+        })
+    }
+
+    /// 重新加载配置(字体、字号等都可能改变)，失败时保持原状态
+    pub fn reload(&mut self, config: Config) -> Result<()> {
+        let fonts = load_fonts(&config)?;
+        let mut renderer = Self::with_fonts(config, fonts, self.width, self.height)?;
+        //保留set_clock设置的时钟，模拟时间继续推进
+        renderer.clock = std::mem::replace(&mut self.clock, Box::new(SystemClock::new()));
+        renderer.last_time = self.last_time;
+        *self = renderer;
+        Ok(())
+    }
+
+    /// 输出大小改变，保留网格中已有的字符
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        let (render_width, render_height) = render_size(&self.config, width, height);
+        self.resized_image = resized_image(width, height)?;
+        self.output = DrawTarget::new(width as i32, height as i32);
+        self.dt = DrawTarget::new(render_width as i32, render_height as i32);
+        self.crt = create_crt(&self.config, width, height, render_width);
+        if let Some(message) = self.message.as_mut() {
+            message.resize(render_width, render_height);
+        }
+        if let Some(clock_face) = self.clock_face.as_mut() {
+            clock_face.reset();
+        }
+        for l in &mut self.layers {
+            l.resize(
+                &self.config,
+                render_width,
+                render_height,
+                self.silhouette.as_ref(),
+                &self.charsets,
+            );
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    /// 替换模拟时钟
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+        self.last_time = Duration::ZERO;
+    }
+
+    /// 在下一帧开始一次故障效果
+    pub fn trigger_glitch(&mut self) {
+        self.glitch.trigger();
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// 推进一帧并绘制，返回绘制的字符数
+    pub fn step(&mut self) -> Result<i32> {
+        //动画按经过的时间推进，帧间隔只影响流畅度(卡顿时最多推进MAX_STEP)
+        let now = self.clock.tick();
+        let dt = now
+            .saturating_sub(self.last_time)
+            .min(MAX_STEP)
+            .as_secs_f32();
+        self.last_time = now;
+
+        self.dt.clear(self.background);
+        //时间改变时更新每层网格的时钟笔画
+        if let Some(clock_face) = self.clock_face.as_mut() {
+            if clock_face.update(now, &self.atlas.fonts) {
+                let (width, height) = (self.dt.width() as u32, self.dt.height() as u32);
+                for l in &mut self.layers {
+                    l.grid.highlight = clock_face.highlight(&l.grid, width, height);
+                }
+            }
+        }
+        //从远到近绘制每一层
+        let mut count = 0;
+        for l in &mut self.layers {
+            l.update(now, dt);
+            count += l.draw(&mut self.dt, &mut self.atlas);
+        }
+        //消息显示在所有层的前面
+        if let Some(message) = self.message.as_mut() {
+            message.update(now, &self.atlas.fonts);
+            count += message.draw(&mut self.dt, &mut self.atlas, now);
+        }
+        //后期处理在渲染分辨率下进行，缩放前的像素更少
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.apply(&mut self.dt);
+        }
+        self.glitch.apply(&mut self.dt, now, dt);
+
+        //缩放至输出大小
+        let image = Image::from_slice_u8(
+            NonZeroU32::new(self.dt.width() as u32).ok_or_else(|| anyhow!("渲染宽度为0"))?,
+            NonZeroU32::new(self.dt.height() as u32).ok_or_else(|| anyhow!("渲染高度为0"))?,
+            self.dt.get_data_u8_mut(),
+            PixelType::U8x4,
+        )?;
+        self.resizer
+            .resize(&image.view(), &mut self.resized_image.view_mut())?;
+
+        self.output
+            .get_data_u8_mut()
+            .copy_from_slice(self.resized_image.buffer());
+        //扫描线等效果需要在放大后的画面上处理
+        if let Some(crt) = self.crt.as_mut() {
+            crt.apply(self.output.get_data_mut(), now.as_secs_f32());
+        }
+        Ok(count)
+    }
+
+    /// 当前画面，每个像素为预乘的0xAARRGGBB
+    pub fn frame(&self) -> &[u32] {
+        self.output.get_data()
+    }
+
+    /// 当前画面的RGBA字节(非预乘)
+    pub fn frame_rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.output.get_data().len() * 4);
+        for p in self.output.get_data() {
+            let a = (p >> 24) & 0xff;
+            let unmul = |c: u32| (c * 255).checked_div(a).map_or(0, |c| c.min(255) as u8);
+            rgba.extend_from_slice(&[
+                unmul((p >> 16) & 0xff),
+                unmul((p >> 8) & 0xff),
+                unmul(p & 0xff),
+                a as u8,
+            ]);
+        }
+        rgba
+    }
+}
+
+/// 不打开窗口，按窗口大小渲染指定帧数后把最后一帧保存为图片
+pub fn export_frame(config: Config, frames: u32, path: &str) -> Result<()> {
+    let (width, height) = (config.window_width, config.window_height);
+    let frame_delay = Duration::from_millis(config.frame_delay);
+    let mut renderer = Renderer::new(config, width, height)?;
+    //导出时不按实际渲染速度计时
+    renderer.set_clock(Box::new(FrameClock::new(frame_delay)));
+    for _ in 0..frames.max(1) {
+        renderer.step()?;
+    }
+    image::save_buffer(
+        path,
+        &renderer.frame_rgba(),
+        width,
+        height,
+        image::ColorType::Rgba8,
+    )?;
+    Ok(())
+}
+
+/// 故障效果的块大小使用最近一层(最后一层)的字号
+fn glitch_block(config: &Config) -> usize {
+    config
+        .layers()
+        .last()
+        .map_or(config.font_size, |layer| layer.font_size)
+        .max(1) as usize
+}
+
+fn create_crt(config: &Config, width: u32, height: u32, render_width: u32) -> Option<Crt> {
+    if config.crt.enabled && render_width > 0 {
+        let scale = width as f32 / render_width as f32;
+        Some(Crt::new(config.crt.clone(), width, height, scale))
+    } else {
+        None
+    }
+}
+
+/// 缩放后的图像缓冲区
+fn resized_image(width: u32, height: u32) -> Result<Image<'static>> {
+    Ok(Image::new(
+        NonZeroU32::new(width).ok_or_else(|| anyhow!("输出宽度为0"))?,
+        NonZeroU32::new(height).ok_or_else(|| anyhow!("输出高度为0"))?,
+        PixelType::U8x4,
+    ))
+}
+
+/// 根据实际输出大小，来调整渲染大小
+pub fn render_size(config: &Config, width: u32, height: u32) -> (u32, u32) {
+    let window_width = width as f64;
+    let window_height = height as f64;
+    let mut logical_size = config.logical_size as f64;
+
+    if logical_size > window_width {
+        logical_size = window_width;
+    }
+
+    let scale = logical_size / window_width;
+
+    let render_width = window_width * scale;
+    let render_height = window_height * scale;
+
+    (render_width as u32, render_height as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_font;
+
+    fn render(config: &Config, frames: u32) -> Vec<u32> {
+        let fonts = vec![load_font(&config.font).unwrap()];
+        let mut renderer = Renderer::with_fonts(config.clone(), fonts, 160, 120).unwrap();
+        renderer.set_clock(Box::new(FrameClock::new(Duration::from_millis(50))));
+        for _ in 0..frames {
+            renderer.step().unwrap();
+        }
+        renderer.frame().to_vec()
+    }
+
+    fn seeded(seed: u64) -> Config {
+        let mut config = Config::default();
+        config.seed = Some(seed);
+        config.logical_size = 160;
+        config
+    }
+
+    #[test]
+    fn same_seed_same_frame() {
+        let frame = render(&seeded(42), 60);
+        //不能只是背景色
+        assert!(frame.iter().any(|p| *p != frame[0]));
+        assert_eq!(frame, render(&seeded(42), 60));
+        assert_ne!(frame, render(&seeded(7), 60));
+    }
+}
@@ -2,12 +2,17 @@
 
 use std::{cell::RefCell, env::current_exe, process::Command, rc::Rc};
 
-use crate::config::{
-    read_config, write_config, CHARACTERS_01, CHARACTERS_JAP, CHARACTERS_JIAGUWEN,
-    CHARACTERS_ZHUANTI,
+use crate::{
+    characters::Charsets,
+    config::{
+        builtin_font, builtin_font_by_name, default_font, font_face, font_faces, load_fonts,
+        read_config, split_font_face, system_font, system_font_families, system_font_family,
+        write_config, Config, BUILTIN_FONTS, CHARACTERS_01, CHARACTERS_JAP,
+    },
 };
+use font_kit::font::Font;
 use native_dialog::{FileDialog, MessageDialog, MessageType};
-use slint::{quit_event_loop, SharedString};
+use slint::{quit_event_loop, ModelRc, SharedString, VecModel};
 
 slint::slint! {
     import { SettingWindow } from "ui/setting.slint";
@@ -17,25 +22,65 @@ pub fn open() {
     let config = Rc::new(RefCell::new(read_config()));
     let window = SettingWindow::new();
 
+    //内置字体之后列出系统中安装的字体
+    let families = Rc::new(system_font_families());
+    let mut font_list: Vec<SharedString> = BUILTIN_FONTS
+        .iter()
+        .map(|f| SharedString::from(f.name))
+        .collect();
+    font_list.push(SharedString::from("字体文件"));
+    font_list.extend(families.iter().map(|f| SharedString::from(f.as_str())));
+    window.set_font_list(ModelRc::from(Rc::new(VecModel::from(font_list))));
+
+    //内置文字: 二进制、日文和带有字符的内置字体
+    let mut characters_list = vec![SharedString::from("二进制"), SharedString::from("日文")];
+    characters_list.extend(
+        BUILTIN_FONTS
+            .iter()
+            .filter(|f| f.characters.is_some())
+            .map(|f| SharedString::from(f.name)),
+    );
+    window.set_characters_list(ModelRc::from(Rc::new(VecModel::from(characters_list))));
+
     let config_update = config.clone();
     let update_window = window.as_weak();
+    let font_cache = FontCache::default();
+    let face_cache = Rc::new(FaceCache::default());
+    let update_faces = face_cache.clone();
     let update_values = move || {
         let cfg = config_update.borrow();
         if let Some(window) = update_window.upgrade() {
             window.set_characters(SharedString::from(cfg.characters()));
             window.set_text_color(SharedString::from(&cfg.color));
-            let font_type = if cfg.font == "2" {
-                "小篆"
-            } else if cfg.font == "3" {
-                "甲骨文"
-            } else if cfg.font == "4" {
-                "永无BUG"
-            } else if cfg.font == "1" {
-                "默认"
+            let font_type = if let Some(font) = builtin_font(&cfg.font) {
+                font.name
+            } else if let Some(family) = system_font_family(&cfg.font) {
+                family
             } else {
                 "字体文件"
             };
             window.set_font_type(SharedString::from(font_type));
+            //字体集可以选择其中的字体
+            let (path, face) = split_font_face(&cfg.font);
+            let faces = if font_type == "字体文件" {
+                cached_faces(path, &update_faces)
+            } else {
+                vec![]
+            };
+            let face = match face {
+                Some(face) => face
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| faces.get(i).cloned())
+                    .unwrap_or_else(|| face.to_string()),
+                None => faces.first().cloned().unwrap_or_default(),
+            };
+            let face_list: Vec<SharedString> = faces
+                .iter()
+                .map(|f| SharedString::from(f.as_str()))
+                .collect();
+            window.set_face_list(ModelRc::from(Rc::new(VecModel::from(face_list))));
+            window.set_font_face(SharedString::from(face.as_str()));
             window.set_font_size(SharedString::from(&format!("{}", cfg.font_size)));
             window.set_spaceing(SharedString::from(&format!("{}", cfg.spaceing)));
             window.set_background_color(SharedString::from(&cfg.background));
@@ -54,6 +99,7 @@ pub fn open() {
                 "不监听"
             }));
             window.set_mutation_rate(SharedString::from(&format!("{}", cfg.mutation_rate)));
+            window.set_font_warning(SharedString::from(&font_warning(&cfg, &font_cache)));
         }
     };
 
@@ -62,6 +108,8 @@ pub fn open() {
     let config_change = config.clone();
     window.on_value_change(move |cmd, val| {
         let config_change = config_change.clone();
+        let families = families.clone();
+        let face_cache = face_cache.clone();
         if (move || -> bool {
             let mut cfg = config_change.borrow_mut();
 
@@ -72,32 +120,31 @@ pub fn open() {
                 if val.len() > 0 {
                     cfg.set_characters(&val);
                 }
-                false
+                //重新检查字体是否包含所有字符
+                true
             } else if cmd == "characters_select" {
-                if val == "小篆" {
-                    cfg.set_characters(CHARACTERS_ZHUANTI);
-                    cfg.font = "2".to_string();
-                } else if val == "甲骨文" {
-                    cfg.set_characters(CHARACTERS_JIAGUWEN);
-                    cfg.font = "3".to_string();
-                } else if val == "日文" {
-                    cfg.set_characters(CHARACTERS_JAP);
-                    cfg.font = "1".to_string();
-                } else {
-                    cfg.set_characters(CHARACTERS_01);
-                    cfg.font = "1".to_string();
+                //字形变换(例如日文镜像)在配置文件的glyph.transform中设置，这里不修改
+                match builtin_font_by_name(&val).and_then(|f| Some((f.id, f.characters?))) {
+                    Some((id, characters)) => {
+                        cfg.set_characters(characters);
+                        cfg.font = id.to_string();
+                    }
+                    None => {
+                        cfg.set_characters(if val == "日文" {
+                            CHARACTERS_JAP
+                        } else {
+                            CHARACTERS_01
+                        });
+                        cfg.font = default_font();
+                    }
                 }
                 true
             } else if cmd == "font" {
-                if val == "小篆" {
-                    cfg.font = "2".to_string();
-                } else if val == "甲骨文" {
-                    cfg.font = "3".to_string();
-                } else if val == "永无BUG" {
-                    cfg.font = "4".to_string();
+                if let Some(font) = builtin_font_by_name(&val) {
+                    cfg.font = font.id.to_string();
                 } else if val == "字体文件" {
                     match FileDialog::new()
-                        .add_filter("TTF字体文件", &["ttf"])
+                        .add_filter("字体文件", &["ttf", "otf", "ttc", "otc"])
                         .show_open_single_file()
                     {
                         Ok(path) => {
@@ -117,10 +164,20 @@ pub fn open() {
                             alert("错误", &format!("{:?}", err));
                         }
                     }
+                } else if families.contains(&val) {
+                    cfg.font = system_font(&val);
                 } else {
-                    cfg.font = "1".to_string();
+                    cfg.font = default_font();
                 }
-                false
+                //重新检查字体是否包含所有字符
+                true
+            } else if cmd == "font_face" {
+                let path = split_font_face(&cfg.font).0.to_string();
+                let faces = cached_faces(&path, &face_cache);
+                if let Some(index) = faces.iter().position(|f| *f == val) {
+                    cfg.font = font_face(&path, index);
+                }
+                true
             } else if cmd == "font_size" {
                 cfg.font_size = val.parse().unwrap_or(12);
                 false
@@ -184,6 +241,48 @@ pub fn open() {
     window.run();
 }
 
+/// 读取过的字体文件路径和其中的字体名称
+type FaceCache = RefCell<Option<(String, Vec<String>)>>;
+
+/// 字体文件中的字体名称，路径改变时才重新读取文件
+fn cached_faces(path: &str, cache: &FaceCache) -> Vec<String> {
+    let mut cache = cache.borrow_mut();
+    let (_, faces) = match cache.take() {
+        Some(loaded) if loaded.0 == path => cache.insert(loaded),
+        _ => cache.insert((path.to_string(), font_faces(path).unwrap_or_default())),
+    };
+    faces.clone()
+}
+
+/// 加载过的字体(Config::fonts()和加载结果)，字体改变时才重新加载
+type FontCache = RefCell<Option<(Vec<String>, Result<Vec<Font>, String>)>>;
+
+/// 检查是否有字符在字体和备用字体中都找不到
+fn font_warning(cfg: &Config, cache: &FontCache) -> String {
+    let names = cfg.fonts();
+    let mut cache = cache.borrow_mut();
+    let (_, fonts) = match cache.take() {
+        Some(loaded) if loaded.0 == names => cache.insert(loaded),
+        _ => {
+            let fonts = load_fonts(cfg).map_err(|err| format!("{:?}", err));
+            cache.insert((names, fonts))
+        }
+    };
+    match fonts {
+        Ok(fonts) => {
+            let uncovered = Charsets::load(cfg, fonts).uncovered;
+            if uncovered.is_empty() {
+                String::new()
+            } else {
+                let list: Vec<&str> = uncovered.iter().take(20).map(|t| t.as_ref()).collect();
+                let more = if uncovered.len() > 20 { " ..." } else { "" };
+                format!("以下字符没有字体可以显示: {}{}", list.join(" "), more)
+            }
+        }
+        Err(err) => format!("字体加载失败: {}", err),
+    }
+}
+
 pub fn open_self() {
     if let Ok(exe_pah) = current_exe() {
         let _ = Command::new(exe_pah).arg("/c").spawn();
@@ -0,0 +1,83 @@
+// 剪影: 根据图片每个格子位置的亮度调整字符的亮度或颜色，让代码雨中显示出图片
+use anyhow::Result;
+use image::{imageops::FilterType, RgbaImage};
+use raqote::Color;
+
+use crate::{
+    config::{SilhouetteConfig, SilhouetteMode},
+    grid::Grid,
+};
+
+/// 一个格子的亮度系数和颜色
+#[derive(Clone, Copy)]
+pub struct Tint {
+    pub brightness: f32,
+    //None表示使用原来的颜色
+    pub color: Option<Color>,
+}
+
+pub struct Silhouette {
+    image: RgbaImage,
+    mode: SilhouetteMode,
+    dim: f32,
+}
+
+impl Silhouette {
+    /// 未设置图片时返回None
+    pub fn load(cfg: &SilhouetteConfig) -> Result<Option<Self>> {
+        if cfg.image.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            image: image::open(&cfg.image)?.to_rgba8(),
+            mode: cfg.mode,
+            dim: cfg.dim.clamp(0.0, 1.0),
+        }))
+    }
+
+    /// 图片等比缩放后居中放在width*height的画面中，计算网格每个格子的亮度和颜色
+    pub fn tints(&self, grid: &Grid, width: u32, height: u32) -> Vec<Tint> {
+        let scale = (width as f32 / self.image.width() as f32)
+            .min(height as f32 / self.image.height() as f32);
+        let fit_width = ((self.image.width() as f32 * scale) as u32).max(1);
+        let fit_height = ((self.image.height() as f32 * scale) as u32).max(1);
+        let image =
+            image::imageops::resize(&self.image, fit_width, fit_height, FilterType::Triangle);
+        let left = (width as f32 - fit_width as f32) / 2.0;
+        let top = (height as f32 - fit_height as f32) / 2.0;
+
+        let mut tints = Vec::with_capacity(grid.columns * grid.rows);
+        for row in 0..grid.rows {
+            for col in 0..grid.columns {
+                //格子中心(字符绘制在基线上方)
+                let x = (col as f32 + 0.5) * grid.cell_width - left;
+                let y = (row as f32 * grid.cell_height - grid.font_size / 2.0) - top;
+                let pixel = if x < 0.0 || y < 0.0 {
+                    None
+                } else {
+                    image.get_pixel_checked(x as u32, y as u32)
+                };
+                tints.push(match pixel {
+                    //图片以外的部分按黑色处理
+                    None => Tint {
+                        brightness: self.dim,
+                        color: None,
+                    },
+                    Some(p) => {
+                        let [r, g, b, a] = p.0;
+                        let luma = (r as f32 * 0.299 + g as f32 * 0.587 + b as f32 * 0.114) / 255.0
+                            * (a as f32 / 255.0);
+                        Tint {
+                            brightness: self.dim + (1.0 - self.dim) * luma,
+                            color: match self.mode {
+                                SilhouetteMode::Brightness => None,
+                                SilhouetteMode::Color => Some(Color::new(255, r, g, b)),
+                            },
+                        }
+                    }
+                });
+            }
+        }
+        tints
+    }
+}